@@ -1,9 +1,9 @@
 use anyhow::{bail, Result};
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use rayon::prelude::*;
 use regex::Regex;
-use rusqlite::{params, types::ValueRef, Connection};
+use rusqlite::{params, types::ValueRef, Connection, OpenFlags};
 use sha2::Digest;
 use simple_logger::SimpleLogger;
 use std::sync::mpsc::{self, Receiver, Sender};
@@ -11,7 +11,7 @@ use std::{
     collections::{HashMap, HashSet},
     fs::File,
     hash::{Hash, Hasher},
-    io::{Read, Write},
+    io::{IsTerminal, Read, Seek, Write},
     os::raw::c_char,
     path::{Path, PathBuf},
 };
@@ -21,6 +21,143 @@ use walkdir::WalkDir;
 static DB_FILENAME: &str = "modland_hash.db";
 static DB_REMOTE: &str = "https://www.dropbox.com/scl/fi/gtk2yri6iizlaeb6b0j0j/modland_hash.db.7z?rlkey=axcrqv54eg2c1yju6vf043ly1&dl=1";
 
+// Set once from --url-prefix at startup. get_url() is called from many places that don't carry
+// `args` around, so this is read globally rather than threaded through every call site.
+static URL_PREFIX: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// Set once from --profile's matching [profiles.NAME] config section at startup, same reasoning as
+// URL_PREFIX: get_db_filename()/get_db_remote() are called from many places that don't carry
+// `args` around. Left unset (falling back to DB_FILENAME/DB_REMOTE) when --profile isn't given or
+// doesn't override that particular field.
+static PROFILE_DB_FILENAME: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+static PROFILE_DB_REMOTE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// Set once from --backend at startup (same reasoning as URL_PREFIX): get_track_info() is the
+// hashing dispatch point and doesn't otherwise need `args` threaded through it. Left unset for
+// "auto" (the default, try every backend in order), or one of the values get_track_info()
+// recognizes ("libopenmpt", "sid") to skip every other backend and force that one.
+static BACKEND_OVERRIDE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+// Set from a SIGINT/SIGTERM handler (see install_shutdown_handler()). Checked from the hot loops
+// in build_database()/download_db()/decompress_db() so Ctrl-C during a long-running build or
+// download stops cleanly: in-flight work finishes, temp files get cleaned up, and the DB is left
+// in a consistent state instead of half-written.
+static SHUTDOWN_REQUESTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+extern "C" fn handle_shutdown_signal(_signum: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+// Installs a handler for SIGINT/SIGTERM that just flips SHUTDOWN_REQUESTED, rather than
+// terminating the process immediately: a bare signal() default action would kill us mid-write to
+// the database or a downloaded .7z, leaving both corrupt for the next run.
+fn install_shutdown_handler() {
+    unsafe {
+        libc::signal(libc::SIGINT, handle_shutdown_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handle_shutdown_signal as *const () as libc::sighandler_t);
+    }
+}
+
+// Formats whose sample data lives in a separate "companion" file next to the main module.
+// Comparisons against either file alone are meaningless, so these get hashed as a unit.
+static COMPANION_EXTENSIONS: &[(&str, &str)] = &[("mdx", "pdx")];
+
+// Custom Amiga player formats libopenmpt doesn't understand at all (hash_file() returns NULL
+// for these). A real fix needs a UADE backend vendored the way libopenmpt is under
+// external/libopenmpt, which hasn't happened yet - so for now these just fall back to
+// sha256-only whole-file matching, same as any other unparsed file. Kept as a named list
+// (rather than silently lumping them in with "corrupt") so the build summary and
+// --list-unparsed can call out *why* no pattern/sample data is available for them.
+static UADE_ONLY_EXTENSIONS: &[&str] = &[
+    "tfmx", "hip", "hipc", "mdat", "cust", "fc", "fc13", "fc14", "emod",
+];
+
+// Parsed subset of a PSID/RSID (C64 SID tune) header, see
+// https://www.hvsc.c64.org/download/C64Music/DOCUMENTS/SID_file_format.txt
+struct SidInfo {
+    title: String,
+    author: String,
+    released: String,
+    songs: u16,
+    start_song: u16,
+    // sha256 of everything after the header, i.e. the actual C64 data/player code.
+    data_hash: [u8; 32],
+}
+
+fn read_sid_cstr(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn parse_sid_header(data: &[u8]) -> Option<SidInfo> {
+    if data.len() < 0x76 || (&data[0..4] != b"PSID" && &data[0..4] != b"RSID") {
+        return None;
+    }
+
+    let data_offset = u16::from_be_bytes([data[6], data[7]]) as usize;
+    let songs = u16::from_be_bytes([data[14], data[15]]);
+    let start_song = u16::from_be_bytes([data[16], data[17]]);
+
+    if data.len() < data_offset {
+        return None;
+    }
+
+    Some(SidInfo {
+        title: read_sid_cstr(&data[0x16..0x36]),
+        author: read_sid_cstr(&data[0x36..0x56]),
+        released: read_sid_cstr(&data[0x56..0x76]),
+        songs,
+        start_song,
+        data_hash: sha2::Sha256::digest(&data[data_offset..]).into(),
+    })
+}
+
+fn needs_uade_backend(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|ext| {
+            UADE_ONLY_EXTENSIONS
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(ext))
+        })
+        .unwrap_or(false)
+}
+
+// Extensions libopenmpt is expected to load successfully. Used only to tell "unsupported
+// format" apart from "corrupt/malformed file of a format we should support" in parse_status.
+static KNOWN_TRACKER_EXTENSIONS: &[&str] = &[
+    "mod", "xm", "it", "s3m", "stm", "mtm", "ult", "far", "ptm", "okt", "669", "amf", "ams",
+    "dbm", "digi", "dsm", "dtm", "gdm", "ice", "imf", "j2b", "m15", "mdl", "med", "mt2", "mtn",
+    "nst", "plm", "psm", "pt36", "ppm", "sfx", "stx", "symmod", "wow",
+];
+
+fn get_format_extension(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+// Classifies a file after hashing so `--build-database` can record *why* it has no pattern
+// data, instead of silently leaving pattern_hash NULL as before.
+fn get_parse_status(filename: &str, parsed_ok: bool) -> &'static str {
+    if parsed_ok {
+        "ok"
+    } else if needs_uade_backend(filename) || get_format_extension(filename) == "sid" {
+        "unsupported"
+    } else if KNOWN_TRACKER_EXTENSIONS.contains(&get_format_extension(filename).as_str()) {
+        "corrupt"
+    } else {
+        "unsupported"
+    }
+}
+
 #[repr(C)]
 struct CSampleData {
     data: *const u8,
@@ -66,8 +203,8 @@ impl CSampleData {
         }
     }
 
-    fn get_text(&self) -> String {
-        get_string_cstr(self.sample_text)
+    fn get_text(&self, charset: &str) -> String {
+        get_string_cstr(self.sample_text, charset)
     }
 }
 
@@ -79,6 +216,23 @@ struct CData {
     sample_count: u32,
     instrument_count: u32,
     channel_count: u32,
+    // Channel-order-invariant pattern hash, see hash_patterns_normalized() in interface.cpp.
+    normalized_pattern_hash: u64,
+    // Patterns + raw sample data, see hash_canonical() in interface.cpp. Catches duplicates that
+    // differ only in container bytes (added ID field, trailing garbage, a re-saved/converted header).
+    canonical_hash: u64,
+    // Set when the module has no orders or nothing but silent patterns, see hash_patterns() in
+    // interface.cpp.
+    is_empty_pattern: u8,
+    // Normalized pattern text, only populated when --dump-patterns is set. Null otherwise.
+    pattern_text: *const c_char,
+    // Non-fatal libopenmpt log messages from loading (truncated samples, suspicious headers,
+    // etc.), newline-separated. Null if loading logged nothing.
+    warnings: *const c_char,
+    // The song message embedded in the module itself (IT/XM/S3M "comment" text), see
+    // get_metadata("message") in interface.cpp. Null if the format doesn't support one or it's
+    // empty.
+    message: *const c_char,
 }
 
 impl CData {
@@ -86,10 +240,44 @@ impl CData {
         unsafe { std::slice::from_raw_parts(self.samples, self.sample_count as _) }
     }
 
-    fn get_instrument_names(&self) -> Vec<String> {
+    // Unlike sample/instrument text, pattern text is generated by us (not decoded from the
+    // module), so it's always plain UTF-8 and needs no charset handling.
+    fn get_pattern_text(&self) -> Option<String> {
+        if self.pattern_text.is_null() {
+            return None;
+        }
+
+        let bytes = unsafe { std::ffi::CStr::from_ptr(self.pattern_text) }.to_bytes();
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    // Log text, like pattern_text, is generated by libopenmpt itself rather than decoded from
+    // the module, so it's plain UTF-8 and needs no charset handling either.
+    fn get_warnings(&self) -> Option<String> {
+        if self.warnings.is_null() {
+            return None;
+        }
+
+        let bytes = unsafe { std::ffi::CStr::from_ptr(self.warnings) }.to_bytes();
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    // Like pattern_text/warnings, get_metadata("message") already comes back as UTF-8 from
+    // libopenmpt, so no charset handling is needed here either.
+    fn get_message(&self) -> Option<String> {
+        if self.message.is_null() {
+            return None;
+        }
+
+        let bytes = unsafe { std::ffi::CStr::from_ptr(self.message) }.to_bytes();
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn get_instrument_names(&self, charset: &str) -> Vec<String> {
         let mut output = Vec::new();
         for i in 0..self.instrument_count {
-            let name = unsafe { get_string_cstr(*self.instrument_names.offset(i as _)) };
+            let name =
+                unsafe { get_string_cstr(*self.instrument_names.offset(i as _), charset) };
             output.push(name);
         }
         output
@@ -97,22 +285,92 @@ impl CData {
 }
 
 extern "C" {
-    fn hash_file(data: *const u8, len: u32, dump_patterns: i32) -> *const CData;
+    fn hash_file(
+        data: *const u8,
+        len: u32,
+        dump_patterns: i32,
+        skip_patterns: i32,
+        skip_sample_hash: i32,
+    ) -> *const CData;
     fn free_hash_data(data: *const CData);
+    fn render_fingerprint(data: *const u8, len: u32, seconds: i32) -> u64;
+    fn get_pattern_hash_version() -> u64;
+    fn get_last_parse_error() -> *const c_char;
+    fn get_last_parse_error_code() -> i32;
 }
 
-fn get_string_cstr(c: *const c_char) -> String {
-    match unsafe { std::ffi::CStr::from_ptr(c).to_str() } {
-        //Ok(s) => if s.is_empty() { String::new() } else { format!("'{}'", s.to_owned()) },
-        Ok(s) => {
-            let t = s.replace('\'', "''");
-            format!("'{}'", t)
-        }
+// Reads the native layer's record of why its most recent hash_file() call failed, if any. Must be
+// called right after hash_file() returns and before any other extern call that might touch the
+// same thread_local state, since it's only valid for the immediately preceding call.
+fn get_native_parse_error() -> Option<String> {
+    let ptr = unsafe { get_last_parse_error() };
+    if ptr.is_null() {
+        return None;
+    }
+    let message = unsafe { std::ffi::CStr::from_ptr(ptr) }.to_string_lossy().into_owned();
+    // code 2 means libopenmpt threw something that wasn't a std::exception, so the message above
+    // ("unknown error") is generic on its own - tag it with the code so it's still distinguishable
+    // from the "no error recorded" case and from an empty std::exception::what().
+    if unsafe { get_last_parse_error_code() } == 2 {
+        Some(format!("{} (non-standard exception)", message))
+    } else {
+        Some(message)
+    }
+}
+
+// CP437 codepoints for byte values 128..=255 (0..127 match ASCII). Used to decode sample and
+// instrument text that isn't valid UTF-8, since Amiga/DOS-era tracker formats commonly store
+// names in CP437 rather than UTF-8, and decoding them as UTF-8 either mangles accented
+// characters or fails outright.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ',
+    'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ',
+    'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕',
+    '╣', '║', '╗', '╝', '╜', '╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦',
+    '╠', '═', '╬', '╧', '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐',
+    '▀', 'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±',
+    '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{00a0}',
+];
+
+fn decode_cp437(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b < 128 {
+                b as char
+            } else {
+                CP437_HIGH[(b - 128) as usize]
+            }
+        })
+        .collect()
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
 
-        Err(_) => "''".to_string(),
+// Decodes raw sample/instrument text bytes per `--sample-charset`. "utf8"/"latin1"/"cp437" force
+// a specific decoding; anything else ("auto", or an unrecognized value) tries UTF-8 first and
+// falls back to CP437, which covers the common case without needing the user to know the exact
+// charset a given file was saved with.
+fn decode_text_bytes(bytes: &[u8], charset: &str) -> String {
+    match charset {
+        "utf8" => String::from_utf8_lossy(bytes).into_owned(),
+        "latin1" => decode_latin1(bytes),
+        "cp437" => decode_cp437(bytes),
+        _ => std::str::from_utf8(bytes)
+            .map(|s| s.to_owned())
+            .unwrap_or_else(|_| decode_cp437(bytes)),
     }
 }
 
+fn get_string_cstr(c: *const c_char, charset: &str) -> String {
+    let bytes = unsafe { std::ffi::CStr::from_ptr(c) }.to_bytes();
+    let decoded = decode_text_bytes(bytes, charset);
+    let escaped = decoded.replace('\'', "''");
+    format!("'{}'", escaped)
+}
+
 #[derive(Clone)]
 struct SampleInfo {
     sample_id: u32,
@@ -120,6 +378,63 @@ struct SampleInfo {
     text: String,
     length_bytes: usize,
     length: usize,
+    // C-level sample attributes, carried straight through from CSampleData so they can be
+    // stored alongside the hash and used as secondary signals (e.g. two samples with the same
+    // data hash but a different fine_tune are the same sound re-tuned, not a true duplicate).
+    c5_speed: u32,
+    pan: u16,
+    volume: u16,
+    global_vol: u16,
+    stereo: u8,
+    bits_per_sample: u8,
+    relative_tone: i8,
+    fine_tune: i8,
+    vib_type: u8,
+    vib_sweep: u8,
+    vib_depth: u8,
+    vib_rate: u8,
+    // Cheap PCM fingerprint for collision research (see --store-sample-fingerprints): two
+    // samples with the same sha256 but different slack-space text, or different sha256 but the
+    // same audio, both show up as sha256 mismatches in the obvious queries. Only written to the
+    // database when that flag is set; computed here regardless since it's nearly free next to
+    // the sha256 hash we're already taking.
+    fingerprint: Option<SampleFingerprint>,
+}
+
+#[derive(Clone)]
+struct SampleFingerprint {
+    head_hash: String,
+    tail_hash: String,
+    rms: f64,
+}
+
+// first/last N bytes hash + a whole-sample RMS amplitude, treating the PCM as 8-bit unsigned
+// when bits_per_sample isn't 16 (close enough for flagging candidates, not exact analysis).
+const FINGERPRINT_EDGE_BYTES: usize = 64;
+
+fn compute_sample_fingerprint(data: &[u8], bits_per_sample: u8) -> SampleFingerprint {
+    let head = &data[..data.len().min(FINGERPRINT_EDGE_BYTES)];
+    let tail = &data[data.len().saturating_sub(FINGERPRINT_EDGE_BYTES)..];
+
+    let rms = if bits_per_sample == 16 && data.len() >= 2 {
+        let sum_sq: f64 = data
+            .chunks_exact(2)
+            .map(|c| {
+                let sample = i16::from_le_bytes([c[0], c[1]]) as f64;
+                sample * sample
+            })
+            .sum();
+        (sum_sq / (data.len() / 2) as f64).sqrt()
+    } else {
+        let sum_sq: f64 = data.iter().map(|&b| { let v = b as f64 - 128.0; v * v }).sum();
+        (sum_sq / data.len() as f64).sqrt()
+    };
+
+    SampleFingerprint {
+        head_hash: format!("{:x}", sha2::Sha256::digest(head)),
+        tail_hash: format!("{:x}", sha2::Sha256::digest(tail)),
+        rms,
+    }
 }
 
 #[derive(Clone, Default)]
@@ -129,12 +444,60 @@ struct TrackInfo {
     filename: String,
     samples: Vec<SampleInfo>,
     instrument_names: Vec<String>,
+    // Only set when --render-fingerprint-seconds is used and the file has no usable pattern hash
+    // (pattern_hash came back as 0, or is_empty_pattern is set).
+    render_hash: Option<u64>,
+    // Set for formats with a companion file (e.g. MDX/PDX) that was found alongside this one.
+    companion_url: Option<String>,
+    pair_hash: Option<String>,
+    // "ok" / "unsupported" / "corrupt", see get_parse_status().
+    parse_status: String,
+    // Reason the native layer gave for hash_file() failing, see get_native_parse_error(). Only
+    // ever set alongside a non-"ok" parse_status.
+    parse_error: Option<String>,
+    // Non-fatal libopenmpt warnings from loading (truncated samples, suspicious headers, etc.),
+    // see CData::get_warnings(). Unlike parse_error, these can be set alongside parse_status
+    // "ok" - the file still loaded, libopenmpt just flagged something about it.
+    warnings: Option<String>,
+    // The song message embedded in the module itself (IT/XM/S3M "comment" text), see
+    // CData::get_message(). Often carries the real author's name even when a file has been
+    // re-ripped under someone else's name.
+    message: Option<String>,
+    format: String,
+    // Which hashing backend produced this hash: "libopenmpt", "sid", or "none" if neither could
+    // parse the file (only the whole-file sha256 is usable for matching then). See Args::backend
+    // to force a single backend instead of the default try-libopenmpt-then-sid order.
+    backend: String,
+    channel_count: u32,
+    sample_count: u32,
+    instrument_count: u32,
+    // Channel-order-invariant pattern hash, see --match-normalized.
+    normalized_pattern_hash: u64,
+    // Patterns + raw sample data, see hash_canonical() in interface.cpp. A third, more specific
+    // match level between pattern_hash and a full sha256: catches duplicates whose container
+    // differs (added ID field, trailing garbage, a .mod re-saved as .wow) but whose musical
+    // content — orders, patterns and sample data — is byte-identical.
+    canonical_hash: u64,
+    // Set when the module has no orders or nothing but silent patterns: pattern_hash for these is
+    // just the bare hash seed, so every such file looks like a duplicate of every other one.
+    // Tracked separately so get_dupes and --match can exclude or report this junk on its own.
+    is_empty_pattern: bool,
+    file_size: u64,
+    // Normalized pattern text, only set when --dump-patterns is used. Not sent over the
+    // worker-pool IPC protocol (it can be large), so it is always None for files parsed by the
+    // worker pool.
+    pattern_text: Option<String>,
 }
 
 #[derive(Default, Debug, Clone)]
 struct DatabaseMeta {
     filename: String,
     samples: Vec<String>,
+    instrument_names: Vec<String>,
+    format: String,
+    channel_count: u32,
+    file_size: u64,
+    sample_count: u32,
 }
 
 impl PartialEq for DatabaseMeta {
@@ -159,22 +522,133 @@ struct Args {
     #[clap(short, long)]
     build_database: Option<String>,
 
+    /// With --build-database, walks the tree and reports file counts, total size, and an
+    /// extension breakdown without parsing a single file or writing a database, so the source
+    /// path and filters can be sanity-checked before committing to a multi-hour run
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Packages the local database for publishing: 7z-compresses it to <out.7z> and writes a
+    /// "<out.7z>.manifest.json" next to it (build date, row counts, hash-algorithm version,
+    /// sha256 of the archive), so a maintainer doesn't have to do this by hand before uploading
+    #[clap(long, value_name = "out.7z")]
+    package_database: Option<String>,
+
+    /// Config file seeding default filter flags (include/exclude paths, extensions, path/sample/
+    /// instrument regexes, channels, format, min/max size, min/max dupes, sort, color,
+    /// output-format), so they don't need to be repeated on every run. CLI flags override it.
+    /// Defaults to ~/.config/modland_hash/config.toml if present.
+    #[clap(long)]
+    config: Option<String>,
+
+    /// Selects a named database profile from the config file's matching [profiles.NAME] section
+    /// (e.g. "modland", "aminet", "personal"), overriding the default DB filename/remote URL with
+    /// that profile's db_filename/db_remote. Affects --build-database, --download-database,
+    /// --check-update, --match-dir and every dupe-listing command, since they all resolve the
+    /// database through get_db_filename()/get_db_remote(). Requires --config or the default config
+    /// file to exist
+    #[clap(long)]
+    profile: Option<String>,
+
     /// Downloads the remote database (automatically performed if it doesn't exist)
     #[clap(short, long)]
     download_database: bool,
 
+    /// With --download-database (or an automatic first-run download), buffers the downloaded .7z
+    /// in memory and decompresses it straight from there instead of writing it to disk first.
+    /// Uses more RAM for the duration of the download but means the compressed archive never
+    /// touches disk. Only applies when the 7z artifact is used; ignored when --db-format
+    /// resolves to "zstd"
+    #[clap(long)]
+    stream_download: bool,
+
+    /// Which compressed database artifact to fetch: "7z" (the original LZMA archive), "zstd"
+    /// (noticeably faster to decompress on low-end machines, at some cost in size), or "auto"
+    /// (the default), which HEAD-checks for a zstd artifact next to the configured 7z remote and
+    /// prefers it when published, falling back to 7z otherwise
+    #[clap(long, default_value = "auto")]
+    db_format: String,
+
+    /// For disk-constrained environments: never keeps a full decompressed copy of the database
+    /// around. The compressed artifact (7z or zstd, whichever --db-format resolves to) is
+    /// downloaded as usual but left compressed on disk; each run decompresses it into a scratch
+    /// file under the OS temp directory, opens that read-only, and (on platforms where an open
+    /// file can be unlinked while still in use) removes the scratch file immediately, so no
+    /// decompressed copy is ever left sitting on disk. This isn't a true "query the archive
+    /// directly" VFS — sqlite still needs a real file to mmap/seek against — but it gets the
+    /// practical benefit of never paying for both the compressed and decompressed copy at once.
+    /// Tags and annotations are unavailable in this mode, since restoring them needs a writable
+    /// connection
+    #[clap(long)]
+    db_compressed: bool,
+
+    /// Checks whether a newer database is available on the remote, without downloading it. Exits
+    /// 0 if up to date, 1 if a newer database is available, 2 if that can't be determined (e.g. no
+    /// database has ever been downloaded here). Handy for a cron job deciding whether to re-run
+    /// with --download-database
+    #[clap(long)]
+    check_update: bool,
+
+    /// Emits a shell completion script for the given shell to stdout and exits, e.g.
+    /// `modland_hash --generate-completions bash > /etc/bash_completion.d/modland_hash`
+    #[clap(long, value_name = "SHELL")]
+    generate_completions: Option<clap_complete::Shell>,
+
+    /// Emits a roff man page to stdout and exits, e.g.
+    /// `modland_hash --generate-man | gzip > modland_hash.1.gz`
+    #[clap(long)]
+    generate_man: bool,
+
     /// Directory to search against the database. If not specified, the current directory will be used.
     #[clap(short, long, default_value = ".")]
     match_dir: String,
 
+    /// Prefix prepended to database paths wherever a URL is printed, e.g. to make them clickable
+    /// links. Defaults to the public modland mirror; pass an empty string to print raw paths
+    /// instead, or a different base if you're running this against your own archive
+    #[clap(long, default_value = "https://ftp.modland.com")]
+    url_prefix: String,
+
     /// Performs recursive scanning (includes sub-directories) when using --match-dir and --build-database
     #[clap(short, long)]
     recursive: bool,
 
+    /// Caps how many directory levels deep --match-dir/--build-database scan, overriding
+    /// --recursive's binary on/off. Example: --max-depth 2 descends two levels of sub-directories
+    /// regardless of whether --recursive is set. 0 scans the given directory only (same as
+    /// omitting --recursive)
+    #[clap(long)]
+    max_depth: Option<usize>,
+
+    /// Follows symlinked directories/files while scanning, instead of the default of leaving them
+    /// unvisited (the safer default, since a symlink cycle under the scanned tree would otherwise
+    /// hang the scan)
+    #[clap(long)]
+    follow_symlinks: bool,
+
+    /// Skips dotfiles/dot-directories (anything whose name starts with '.') while scanning
+    #[clap(long)]
+    skip_hidden: bool,
+
+    /// Comma-separated glob patterns ('*' and '?' wildcards, case-insensitive, matched against the
+    /// whole basename) of files to skip entirely during scanning rather than parsing them and
+    /// recording them as "unsupported". Example: --ignore-patterns ".listing,*.txt,*.nfo,*.diz,readme*"
+    #[clap(long, default_value = ".listing,*.txt,*.nfo,*.diz")]
+    ignore_patterns: String,
+
     /// Instead of matching on hash or pattern hash match the samples in the files
     #[clap(long)]
     match_samples: bool,
 
+    /// With --match-samples, skips samples shorter than this many bytes, so tiny clicks and
+    /// drum hits shared by thousands of unrelated songs don't dominate the report
+    #[clap(long, default_value_t = 0)]
+    min_sample_length: usize,
+
+    /// With --match-samples, only reports a sample if at least this many duplicates were found
+    #[clap(long, default_value_t = 1)]
+    min_sample_dupes: usize,
+
     /// Search the database for samples matching a certain length (length is in samples)
     #[clap(long)]
     find_samples_with_length: Option<usize>,
@@ -183,6 +657,19 @@ struct Args {
     #[clap(long)]
     find_samples_with_length_bytes: Option<usize>,
 
+    /// Hashes a single sample (either a 64-char hex sha256, or a path to a WAV file previously
+    /// written by --export-samples) and lists every database song containing it. Example:
+    /// --find-sample ./unknown_kick.wav
+    #[clap(long)]
+    find_sample: Option<String>,
+
+    /// Searches stored song messages (the IT/XM/S3M "comment" text, see --build-database) for a
+    /// regex match, printing the url and message of every song that matches (case-insensitive).
+    /// Often turns up the real ripper/author even when the file itself has been retitled. Example:
+    /// --search-message "ripped by"
+    #[clap(long)]
+    search_message: Option<String>,
+
     /// Skips files with these extensions if any duplicates are found. Example: --skip-file-extensions "mdx,pdx" will skip all duplicates that contain .mdx and .pdx files (case-insensitive)
     #[clap(long, default_value = "")]
     exclude_file_extensions: String,
@@ -199,10 +686,155 @@ struct Args {
     #[clap(long, default_value = "")]
     include_paths: String,
 
+    /// Excludes matches whose path matches this regex (or simple glob with `*`/`?`), evaluated
+    /// against the lowercased filename. Example: --exclude-path-regex "coop/*" excludes anything under any coop/ directory
+    #[clap(long, default_value = "")]
+    exclude_path_regex: String,
+
+    /// Includes matches only if the path matches this regex (or simple glob with `*`/`?`), evaluated
+    /// against the lowercased filename. Example: --include-path-regex "demos/.*2023.*"
+    #[clap(long, default_value = "")]
+    include_path_regex: String,
+
+    /// Compares paths exactly as stored (case-sensitive, `/` and `\` treated as different
+    /// characters) instead of the default case-insensitive, separator-unified matching used by
+    /// --include-paths/--exclude-paths and the path regex/glob filters
+    #[clap(long)]
+    strict_path_matching: bool,
+
+    /// Includes matches only with this exact channel count, as reported by the native layer.
+    /// Example: --channels 4 limits results to 4-channel modules
+    #[clap(long)]
+    channels: Option<u32>,
+
+    /// Includes matches only with one of these formats. Example: --format "mod,xm,it"
+    #[clap(long, default_value = "")]
+    format: String,
+
+    /// Skips files smaller than this size in bytes during scanning and matching. Example:
+    /// --min-size 1024 skips anything under 1KB
+    #[clap(long)]
+    min_size: Option<u64>,
+
+    /// Skips files larger than this size in bytes during scanning and matching. Example:
+    /// --max-size 10485760 skips anything over 10MB
+    #[clap(long)]
+    max_size: Option<u64>,
+
+    /// Reads the set of files to process from a newline-separated list instead of walking a
+    /// directory. Use "-" to read the list from stdin. Example: --files-from files.txt
+    #[clap(long, default_value = "")]
+    files_from: String,
+
+    /// Makes match_dir's exit code reflect the outcome instead of always exiting 0: 0 = no
+    /// duplicates found, 1 = duplicates found, 2 = errors occurred while scanning (takes
+    /// priority over 1). Useful for CI-style upload gates that branch on the exit status.
+    #[clap(long)]
+    exit_code_on_match: bool,
+
+    /// Suppresses non-actionable output ("Matching <file>", "No matches found!"), printing only
+    /// found matches
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// With --match-dir, checks a local file's size and sha256 against `files` before running it
+    /// through the full parser: a size+hash hit is reported as an exact match with no parsing at
+    /// all, which is much faster when triaging a huge incoming dump where most files are
+    /// byte-identical to something already in the database. Files that don't hit this cheap
+    /// check still fall through to the normal full parse, so pattern/canonical/render matches are
+    /// unaffected
+    #[clap(long)]
+    quick: bool,
+
+    /// Increases log verbosity; -v surfaces timing and skipped-file info, -vv also surfaces DB
+    /// query details
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Writes duplicate-listing/match-run results to this file instead of stdout, in the format
+    /// chosen by --output-format. Progress/status messages still go to stdout.
+    #[clap(long)]
+    output_file: Option<String>,
+
+    /// Format used for --output-file (or stdout if not set): "text", "json", "csv" or "html"
+    /// (a self-contained report with collapsible duplicate groups and a search box)
+    #[clap(long, default_value = "text")]
+    output_format: String,
+
+    /// Colorize terminal output: "auto" (color if stdout is a terminal and NO_COLOR is unset),
+    /// "always" or "never"
+    #[clap(long, default_value = "auto")]
+    color: String,
+
+    /// How progress is reported while scanning/hashing: "bars" draws indicatif progress bars
+    /// (default), "json" instead emits newline-delimited JSON events to stderr (one object per
+    /// file, with "phase", "current_file", "processed" and "total" fields) for tooling that
+    /// pipes modland_hash's output and can't render bars, e.g. a web upload pipeline
+    #[clap(long, default_value = "bars")]
+    progress: String,
+
+    /// Minimum number of entries a duplicate group must have to be listed. Example: --min-dupes 3
+    /// only shows songs duplicated at least 3 times
+    #[clap(long, default_value = "2")]
+    min_dupes: usize,
+
+    /// Maximum number of entries a duplicate group may have to be listed; groups larger than this
+    /// are skipped. Example: --max-dupes 10 excludes pathological groups like hundreds of empty
+    /// files sharing a hash
+    #[clap(long)]
+    max_dupes: Option<usize>,
+
+    /// By default, pattern_hash duplicate groups exclude files with no orders or nothing but
+    /// silent patterns: every such file hashes to the same degenerate value, so without this
+    /// exclusion they'd all show up as one giant meaningless duplicate group. Pass this flag to
+    /// include them anyway
+    #[clap(long)]
+    include_empty_patterns: bool,
+
+    /// With --list-duplicates-in-database, also reports groups of files whose full
+    /// instrument-name lists (XM/IT) match once case and surrounding whitespace are normalized
+    /// away. Catches dupes whose sample data was re-encoded (so hash_id/pattern_hash no longer
+    /// match) but whose instrument text survived the re-encode untouched
+    #[clap(long)]
+    instrument_name_duplicates: bool,
+
+    /// With --list-duplicates-in-database, suppresses duplicate groups where every member lives
+    /// under the same directory - those are usually just a mirror's own internal re-listing of a
+    /// song rather than the more interesting cross-artist/cross-section dupes (e.g. the same song
+    /// filed under two composers). Takes an optional path-depth, e.g. --cross-dir-only 2 treats
+    /// the last two path components as one "directory" instead of just the immediate parent, for
+    /// collections that split a single artist across several small subfolders. Defaults to 1
+    #[clap(long, num_args = 0..=1, default_missing_value = "1")]
+    cross_dir_only: Option<usize>,
+
+    /// Marks files whose path starts with <PREFIX> as canonical (e.g. the curated
+    /// /pub/modules tree, as opposed to a /incoming holding area). With --list-duplicates-in-database,
+    /// duplicate groups made up entirely of canonical entries are hidden (a mirror listing itself
+    /// twice isn't a dupe worth reporting), and remaining groups are phrased one-directionally as
+    /// "candidate X duplicates canonical Y" instead of a flat entry list
+    #[clap(long)]
+    canonical_path: Option<String>,
+
+    /// With --list-duplicates-in-database, emits one newline-delimited JSON object per duplicate group
+    /// instead of the text/--output-format report: {"kind": "hash"|"pattern_hash"|"instrument_names", "members":
+    /// [{"url", "format", "channel_count", "file_size", "samples"}, ...]}. Meant for cleanup
+    /// scripts that want to consume groups programmatically rather than parse decorated text
+    #[clap(long)]
+    duplicates_json: bool,
+
     /// Includes matches only if one of the duplicates matches the specified regexp pattern for sample names. Example: --include-sample-name ".*ripped.*" will include duplicates where one of the tracks' sample names contains "ripped"
     #[clap(long, default_value = "")]
     include_sample_name: String,
 
+    /// Restricts --include-sample-name to one heuristic class of sample-text line: "name" (looks
+    /// like an actual sample name), "message" (looks like tracker greeting/credit text instead),
+    /// or "any" (the default, no restriction). The heuristic weighs printable-character ratio,
+    /// word count and a list of common greeting keywords ("greetings", "ripped by", "www.", etc);
+    /// good enough to keep a sample-name regex search from matching on greeting text, not a
+    /// proper language classifier
+    #[clap(long, default_value = "any")]
+    sample_name_class: String,
+
     /// Displays duplicate results only if one of the entries includes a matching filename. Example: --search-filename ".*north.*" will include results only if one of the entries has "north" in it (case-insensitive)
     #[clap(long, default_value = "")]
     search_filename: String,
@@ -211,6 +843,15 @@ struct Args {
     #[clap(short, long)]
     print_sample_names: bool,
 
+    /// Includes matches only if one of the duplicates has a matching instrument name (XM/IT).
+    /// Example: --include-instrument-name ".*ripped.*"
+    #[clap(long, default_value = "")]
+    include_instrument_name: String,
+
+    /// Enables printing of instrument names (XM/IT), alongside sample names
+    #[clap(long)]
+    print_instrument_names: bool,
+
     /// Lists existing duplicates in the database
     #[clap(short, long)]
     list_duplicates_in_database: bool,
@@ -219,1010 +860,5795 @@ struct Args {
     #[clap(long)]
     list_database: bool,
 
-    /// Primarily a debug option to allow dumping of pattern data when building the database and matching entries
+    /// Finds songs whose sample set is a strict superset of another song's sample set (e.g. megamixes or extended versions)
     #[clap(long)]
-    dump_patterns: bool,
-}
+    find_supersets: bool,
 
-struct Filters {
-    include_paths: Vec<String>,
-    include_file_extensions: Vec<String>,
-    exclude_paths: Vec<String>,
-    exclude_file_extensions: Vec<String>,
-    sample_search: Option<Regex>,
-    search_filename: Option<Regex>,
-}
+    /// Aggregates database duplicates by directory, e.g. "98% of /pub/favourites/X duplicates
+    /// files under /pub/modules/Y", to spot whole duplicated folders instead of per-file listings
+    #[clap(long)]
+    dir_duplicate_summary: bool,
 
-impl Filters {
-    fn init_filter(filter: &str, prefix: &str) -> Vec<String> {
-        if filter.is_empty() {
-            return Vec::new();
-        }
+    /// Orders --list-duplicates-in-database/--list-database groups by "path" (default), "name"
+    /// (basename only), "size" (total bytes in the group, biggest first) or "dupes" (entry
+    /// count, most duplicated first)
+    #[clap(long, default_value = "path")]
+    sort: String,
 
-        let mut output = Vec::new();
+    /// Caps the number of groups printed by --list-duplicates-in-database/--list-database,
+    /// applied after --sort. Example: --sort dupes --limit 20 shows the 20 most-duplicated songs
+    #[clap(long)]
+    limit: Option<usize>,
 
-        for t in filter.split(',') {
-            output.push(format!("{}{}", prefix, t));
-        }
+    /// Exports every sample in the file(s) under --match-dir as individual .wav files into the given directory
+    #[clap(long)]
+    export_samples: Option<String>,
 
-        output
-    }
+    /// Prints everything known about a single module: sha256, pattern hash, per-sample hashes
+    /// and lengths, instrument names, format/channel/size metadata, and every database hit
+    /// (sha256, pair, canonical, pattern, normalized pattern and render-fingerprint matches)
+    #[clap(long)]
+    inspect: Option<String>,
 
-    fn new(args: &Args) -> Filters {
-        let sample_search = if !args.include_sample_name.is_empty() {
-            Some(Regex::new(&args.include_sample_name.to_ascii_lowercase()).unwrap())
-        } else {
-            None
-        };
+    /// For files with no usable pattern hash (hash==0, or no notes at all), renders the first N seconds of audio and hashes that instead, so byte-different re-saves can still be matched
+    #[clap(long)]
+    render_fingerprint_seconds: Option<i32>,
 
-        let search_filename = if !args.search_filename.is_empty() {
-            Some(Regex::new(&args.search_filename.to_ascii_lowercase()).unwrap())
-        } else {
-            None
-        };
+    /// Walks a local modland mirror and reports three things relative to the database: files on
+    /// disk missing from the database, database entries missing on disk, and files whose sha256
+    /// no longer matches what the database has on record — an integrity check between a mirror
+    /// and the database it was supposedly built from
+    #[clap(long)]
+    verify: Option<String>,
+
+    /// Audits every pattern_hash duplicate group for likely hash collisions: with only a 63-bit
+    /// hash, two unrelated songs can land on the same value, and without this they're
+    /// indistinguishable from real duplicates. A group whose members don't all agree on
+    /// channel_count/sample_count is flagged as a likely collision rather than a true duplicate;
+    /// pass --audit-reverify-dir to additionally re-parse local copies (if found) and confirm
+    /// whether the pattern_hash actually collides
+    #[clap(long)]
+    audit_pattern_collisions: bool,
 
-        Filters {
-            include_paths: Self::init_filter(&args.include_paths.to_ascii_lowercase(), ""),
-            include_file_extensions: Self::init_filter(
-                &args.include_file_extensions.to_ascii_lowercase(),
-                ".",
-            ),
-            exclude_paths: Self::init_filter(&args.exclude_paths.to_ascii_lowercase(), ""),
-            exclude_file_extensions: Self::init_filter(
-                &args.exclude_file_extensions.to_ascii_lowercase(),
-                ".",
-            ),
-            sample_search,
-            search_filename,
-        }
-    }
+    /// With --audit-pattern-collisions, re-parses each flagged group's members under this local
+    /// mirror directory (same url-to-path resolution as --verify) to confirm the pattern_hash
+    /// really does collide, rather than relying on stored channel/sample counts alone
+    #[clap(long)]
+    audit_reverify_dir: Option<String>,
 
-    fn starts_with(filename: &str, tests: &[String], default_val: bool) -> bool {
-        if tests.is_empty() {
-            default_val
-        } else {
-            tests.iter().any(|t| filename.starts_with(t))
-        }
-    }
+    /// Dumps the database's files/samples/instruments tables as Parquet for offline analysis in
+    /// pandas/polars, e.g. "--export parquet:./out" writes out/{files,samples,instruments}.parquet.
+    /// The "parquet:" scheme prefix is required; it's the only export kind supported for now
+    #[clap(long)]
+    export: Option<String>,
 
-    fn ends_with(filename: &str, tests: &[String], default_val: bool) -> bool {
-        if tests.is_empty() {
-            default_val
-        } else {
-            tests.iter().any(|t| filename.ends_with(t))
-        }
-    }
+    /// Writes a flat "sha256<TAB>path" list (one line per database entry, plus a trailing
+    /// "<TAB>pattern_hash" column when the entry has one) for tools that only understand plain
+    /// checksum lists, e.g. archive dedupe/moderation scripts that don't speak SQLite
+    #[clap(long)]
+    export_hashlist: Option<String>,
 
-    // Apply all the filters
-    fn apply_filter(&self, input: &[DatabaseMeta], skip_level: usize) -> Vec<DatabaseMeta> {
-        let mut output: Vec<DatabaseMeta> = Vec::new();
+    /// Tags every database entry matching <MATCH> with <TAG>, e.g. "--tag <sha256> verified-dupe"
+    /// or "--tag 'coop/*.mod' needs-review". <MATCH> is a full sha256 hash_id if it looks like one,
+    /// otherwise a regex/glob against the url. Tags survive --download-database re-downloads: they're
+    /// stashed to a sidecar file before the database is overwritten and restored afterward
+    #[clap(long, num_args = 2, value_names = ["MATCH", "TAG"])]
+    tag: Option<Vec<String>>,
 
-        for i in input {
-            let filename = i.filename.to_ascii_lowercase();
+    /// Lists every database entry carrying <TAG> (see --tag)
+    #[clap(long)]
+    filter_tag: Option<String>,
 
-            if !Self::starts_with(&filename, &self.exclude_paths, false)
-                && !Self::ends_with(&filename, &self.exclude_file_extensions, false)
-                && Self::starts_with(&filename, &self.include_paths, true)
-                && Self::ends_with(&filename, &self.include_file_extensions, true)
-            {
-                output.push(i.clone());
-            }
-        }
+    /// Imports a "sha256,label" CSV of known sample provenance (e.g. "a1b2...,ST-01/strings1")
+    /// into the database's `annotations` table. Re-importing a hash replaces its label, so a
+    /// corrected provenance sheet can just be re-run. Labels then show up in --match-samples
+    #[clap(long)]
+    import_annotations: Option<String>,
 
-        if let Some(re) = self.search_filename.as_ref() {
-            let mut found_filename = false;
+    /// Exports a graph of sample reuse across the whole database: one node per song, one edge
+    /// per pair of songs that share at least one sample hash, weighted by how many they share.
+    /// The output format is picked from the file extension: "--export-sample-graph out.gexf" or
+    /// "out.dot", for loading into Gephi or Graphviz to visualize sample lineage across modland
+    #[clap(long)]
+    export_sample_graph: Option<String>,
 
-            for file in &output {
-                if re.is_match(&file.filename.to_ascii_lowercase()) {
-                    found_filename = true;
-                    break;
-                }
-            }
+    /// For files under --match-dir that exactly match a database entry (same sha256), suggests
+    /// renaming the local file to the canonical modland filename, e.g. turning an incoming
+    /// "final2.mod" into "Artist - Title.mod" before it's filed into the mirror
+    #[clap(long)]
+    suggest_names: bool,
 
-            if !found_filename {
-                return Vec::new();
-            }
-        }
+    /// With --suggest-names, also writes a shell script of "mv" commands performing the
+    /// suggested renames to this path, instead of only printing the suggestions
+    #[clap(long)]
+    rename_script: Option<String>,
 
-        if let Some(re) = self.sample_search.as_ref() {
-            for file in &output {
-                for sample in &file.samples {
-                    if re.is_match(&sample.to_ascii_lowercase()) {
-                        if output.len() >= skip_level {
-                            return output;
-                        } else {
-                            return Vec::new();
-                        }
-                    }
-                }
-            }
+    /// With --match-dir, writes an M3U playlist of every local file that had no database match
+    /// at all, so the genuinely new material can be listened through without re-running the scan
+    #[clap(long)]
+    write_unmatched_playlist: Option<String>,
 
-            return Vec::new();
-        }
+    /// With --match-dir, only reports files with zero database matches (sha256, pair, pattern and
+    /// render-fingerprint all come up empty), instead of the usual per-file match/no-match report.
+    /// Meant for the common moderation question of "which of these files are new to modland?"
+    #[clap(long)]
+    only_unmatched: bool,
 
-        if output.len() >= skip_level {
-            output
-        } else {
-            Vec::new()
-        }
-    }
-}
+    /// With --match-dir --only-unmatched, copies each unmatched file into this directory
+    /// (created if missing) instead of just listing it, so new material can be staged for
+    /// upload in one pass
+    #[clap(long)]
+    unmatched_staging_dir: Option<String>,
 
-// Get files for a given directory
-fn get_files(path: &str, recurse: bool) -> Vec<String> {
-    if !Path::new(path).exists() {
-        println!(
-            "Path/File \"{}\" doesn't exist. No file(s) will be processed.",
-            path
-        );
-        return Vec::new();
-    }
+    /// Copies every file under <SRC> that has no database match (sha256, pair, or non-empty pattern
+    /// hash; canonical-hash and render-fingerprint hits are not checked) into <DEST>, laid out as "<format>/<artist>/<file>" (artist
+    /// guessed from the file's immediate parent directory under <SRC>), logging the rest as
+    /// skipped duplicates. Automates the manual sort-the-incoming-folder pass end to end
+    #[clap(long, num_args = 2, value_names = ["SRC", "DEST"])]
+    import: Option<Vec<String>>,
 
-    // Check if "path" is a single file
-    let md = std::fs::metadata(path).unwrap();
+    /// Lists database entries whose parse_status isn't "ok" (unsupported format or corrupt file), grouped by format
+    #[clap(long)]
+    list_unparsed: bool,
 
-    if md.is_file() {
-        return vec![path.to_owned()];
-    }
+    /// Runs native file parsing in isolated child worker processes during --build-database, so a
+    /// file that crashes libopenmpt only causes that one file to be logged as corrupt and skipped,
+    /// instead of taking down the whole (potentially multi-hour) build
+    #[clap(long)]
+    worker_pool: bool,
 
-    let spinner_style = ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}")
-        .unwrap()
-        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ");
+    /// With --worker-pool, kills and reports as "timed_out" any worker that takes longer than
+    /// this many seconds to parse a single file, so a pathological file can't hang the build
+    #[clap(long)]
+    worker_timeout_seconds: Option<u64>,
 
-    let pb = ProgressBar::new(0);
-    pb.set_style(spinner_style);
-    pb.set_prefix(format!("Fetching list of files... [{}/?]", 0));
+    /// With --worker-pool on Unix, caps each worker process's virtual memory to this many
+    /// megabytes so a pathological file can't exhaust system memory
+    #[clap(long)]
+    worker_memory_limit_mb: Option<u64>,
 
-    let max_depth = if !recurse { 1 } else { usize::MAX };
+    /// Internal: parses a single file and prints its encoded TrackInfo to stdout. Used to
+    /// re-invoke this executable as a worker process when --worker-pool is set; not for end users.
+    #[clap(long, hide = true)]
+    parse_worker: Option<String>,
 
-    let files: Vec<String> = WalkDir::new(path)
-        .max_depth(max_depth)
-        .into_iter()
-        .filter_map(|e| {
-            let file = e.unwrap();
-            let metadata = file.metadata().unwrap();
-
-            if let Some(filename) = file.path().to_str() {
-                if metadata.is_file()
-                    && !filename.ends_with(".listing")
-                    && !filename.contains("modland_hash")
-                {
-                    pb.set_message(filename.to_owned());
-                    return Some(filename.to_owned());
-                }
-            }
-            None
-        })
-        .collect();
-    files
-}
+    /// Panics immediately on the first unreadable file, permission error or non-UTF-8 path
+    /// instead of logging it and continuing (the old behavior, before per-file errors were collected)
+    #[clap(long)]
+    fail_fast: bool,
+
+    /// Charset used to decode sample/instrument text: "auto" (try UTF-8, fall back to CP437),
+    /// "utf8", "cp437" or "latin1". Amiga/DOS-era modules are commonly CP437, which "auto" handles
+    /// without needing this set explicitly
+    #[clap(long, default_value = "auto")]
+    sample_charset: String,
+
+    /// Selects which hashing backend parses each file: "auto" (the default) tries libopenmpt
+    /// first and falls back to the built-in SID header parser; "libopenmpt" or "sid" forces only
+    /// that one, reporting "none" (sha256-only matching) for files it can't handle. Only these
+    /// two backends are implemented today, but every file funnels through get_track_info(), which
+    /// is where a real per-extension/signature dispatcher (libxmp, UADE, ...) would plug in once
+    /// more backends exist. Whichever backend actually produced a file's hash is recorded in
+    /// files.backend
+    #[clap(long, default_value = "auto")]
+    backend: String,
 
-fn get_url(filename: &str) -> String {
-    let filename = filename.replace(' ', "%20");
-    let filename = filename.replace('\'', "%27");
-    format!("https://ftp.modland.com{}", filename)
-}
+    /// Primarily a debug option to allow dumping of pattern data when building the database and matching entries
+    #[clap(long)]
+    dump_patterns: bool,
 
-fn get_stored_url(filename: &str) -> String {
-    let filename = filename.replace(' ', "%20");
-    filename.replace('\'', "%27")
-}
+    /// With --dump-patterns, writes each song's normalized pattern text to "<sha256>.txt" under
+    /// this directory instead of printing it, so two suspected dupes can be diffed with standard
+    /// tools (diff, vimdiff, ...) instead of opening a tracker. Pattern text doesn't cross the
+    /// worker-pool IPC boundary, so this has no effect together with --worker-pool
+    #[clap(long)]
+    dump_patterns_dir: Option<String>,
 
+    /// With --dump-patterns and --build-database, also stores each song's normalized pattern
+    /// text in the database (files.pattern_text), so --diff-patterns and friends don't need the
+    /// original file kept around on disk
+    #[clap(long)]
+    store_pattern_text: bool,
 
-// Fetches info for a track/song
-fn get_track_info(filename: &str, dump_patterns: bool) -> TrackInfo {
-    // Calculate sha256 of the file
-    let mut file = File::open(filename).unwrap();
-    let mut file_data = Vec::new();
-    file.read_to_end(&mut file_data).unwrap();
-    let hash = sha2::Sha256::digest(&file_data);
-    let dump_patterns = if dump_patterns { 1 } else { 0 };
+    /// With --build-database, also stores a short per-sample PCM fingerprint (first/last 64
+    /// bytes hash + whole-sample RMS) in samples.fingerprint_*, for later research into
+    /// sha256-equal samples that are genuinely different (embedded text in slack space) and
+    /// sha256-different samples that are actually the same audio. Has no effect with
+    /// --no-sample-hashes, since there's no PCM data to fingerprint
+    #[clap(long)]
+    store_sample_fingerprints: bool,
 
-    let song_data = unsafe { hash_file(file_data.as_ptr(), file_data.len() as _, dump_patterns) };
+    /// With --build-database, the path prefix to strip from each scanned file's absolute path
+    /// before storing it as files.url, instead of the database's own scan root. Useful when
+    /// rebuilding a database from a different mount point/drive letter than the one it was
+    /// originally built from, so the stored urls still match
+    #[clap(long)]
+    path_prefix_strip: Option<String>,
 
-    let mut track_info = TrackInfo {
-        filename: filename.to_owned(),
-        sha256_hash: format!("{:x}", hash),
-        ..Default::default()
-    };
+    /// With --build-database, a path prefix to prepend to each stored files.url after stripping,
+    /// so a database built from e.g. "/mnt/modland/mods" can still look like the mirror's real
+    /// "/mods" layout
+    #[clap(long, default_value = "")]
+    path_prefix_add: String,
+
+    /// Skips per-sample sha256 hashing (and the sample half of canonical_hash), for a quick
+    /// triage pass over a corpus with hundreds of MB of sample data per file. Pattern matching
+    /// still works; sample-level dupe detection doesn't. Conflicts with --samples-only
+    #[clap(long, conflicts_with = "samples_only")]
+    no_sample_hashes: bool,
+
+    /// Skips pattern hashing (pattern_hash, normalized_pattern_hash and the pattern half of
+    /// canonical_hash), hashing only sample data. Useful when only sample-level dupe detection
+    /// is needed and the file's patterns aren't of interest. Conflicts with --no-sample-hashes
+    #[clap(long, conflicts_with = "no_sample_hashes")]
+    samples_only: bool,
+
+    /// Loads two local files (e.g. "--diff-patterns a.mod b.xm") and shows which patterns/rows
+    /// differ between their normalized pattern text, to answer "why did these two only
+    /// pattern-match partially?" without opening a tracker
+    #[clap(long, num_args = 2, value_names = ["FILE_A", "FILE_B"])]
+    diff_patterns: Option<Vec<String>>,
+
+    /// Hashes every file under <DIR> without writing a database, reporting per-stage timings
+    /// (I/O, sha256, libopenmpt parse, sample hashing) and MB/s, both single- and
+    /// multi-threaded — for evaluating performance changes like faster hashes or mmap I/O
+    #[clap(long)]
+    bench: Option<String>,
 
-    if !song_data.is_null() {
-        let hash_id = unsafe { (*song_data).hash };
-        let samples = unsafe { (*song_data).get_samples() };
-        track_info.pattern_hash = hash_id;
+    /// Matches on normalized_pattern_hash instead of pattern_hash: channels are grouped by
+    /// content and empty ones dropped before hashing, so a re-save that reorders channels, or a
+    /// MOD->XM conversion that pads in extra empty channels, still counts as a match
+    #[clap(long)]
+    match_normalized: bool,
+}
 
-        for sample in samples {
-            let sha256_hash = if let Some(data) = sample.get_data() {
-                let hash = sha2::Sha256::digest(data);
-                format!("'{:x}'", hash)
-            } else {
-                "NULL".to_string()
-            };
+// Per-format (extension for successfully parsed files, parse_status otherwise) counts and total
+// bytes, accumulated during build_database so the summary can show which formats a
+// libopenmpt/back-end regression made stop parsing.
+struct FormatStats {
+    entries: std::sync::Mutex<HashMap<String, (u64, u64)>>,
+}
 
-            track_info.samples.push(SampleInfo {
-                sample_id: sample.sample_id,
-                sha256_hash,
-                text: sample.get_text(),
-                length_bytes: sample.length_bytes as _,
-                length: sample.length as _,
-            });
+impl FormatStats {
+    fn new() -> FormatStats {
+        FormatStats {
+            entries: std::sync::Mutex::new(HashMap::new()),
         }
+    }
 
-        let instrument_names = unsafe { (*song_data).get_instrument_names() };
-
-        for name in instrument_names {
-            track_info.instrument_names.push(name);
-        }
+    fn record(&self, format: &str, file_size: u64) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(format.to_string()).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += file_size;
+    }
 
-        //let sample_names = unsafe { get_string_cstr((*song_data).sample_names) };
-        //track_info.sample_names = sample_names;
-        //track_info.pattern_hash = hash_id;
+    fn print_table(&self) {
+        let entries = self.entries.lock().unwrap();
+        let mut rows: Vec<(&String, &(u64, u64))> = entries.iter().collect();
+        rows.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(b.0)));
 
-        unsafe { free_hash_data(song_data) };
+        println!("Format breakdown:");
+        for (format, (count, bytes)) in rows {
+            println!("  {:<12} {:>10} files {:>14} bytes", format, count, bytes);
+        }
     }
-
-    track_info
 }
 
-// Get the target filename
-fn get_db_filename() -> String {
-    let p = std::env::current_exe().unwrap();
-    let path = Path::new(&p);
-    let path = path.parent().unwrap().join(DB_FILENAME);
-    path.into_os_string().into_string().unwrap()
+// Collects per-file errors (unreadable files, permission errors, non-UTF-8 paths) during a run
+// instead of letting them panic the whole process. With --fail-fast, callers panic immediately
+// on the first error instead of recording it here, restoring the old behavior.
+struct RunErrors {
+    entries: std::sync::Mutex<Vec<String>>,
 }
 
-enum DbCommand {
-    Insert(String), // Example command to insert a string
-    Quit,           // Example command to query a string
-}
+impl RunErrors {
+    fn new() -> RunErrors {
+        RunErrors {
+            entries: std::sync::Mutex::new(Vec::new()),
+        }
+    }
 
-fn run_build_db_thread(filename: String, rx: Receiver<DbCommand>) -> Result<()> {
-    let conn = Connection::open(filename).expect("Failed to open database");
+    fn record(&self, context: &str, err: impl std::fmt::Display) {
+        self.entries
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}", context, err));
+    }
 
-    conn.execute("PRAGMA foreign_keys = ON", [])?;
+    fn has_errors(&self) -> bool {
+        !self.entries.lock().unwrap().is_empty()
+    }
 
-    conn.execute(
-        "CREATE TABLE files (
-        song_id INTEGER PRIMARY KEY, 
-        hash_id TEXT NOT NULL, 
-        pattern_hash INTEGER, 
-        url TEXT NOT NULL
-        )",
-        [],
-    )
-    .unwrap();
+    fn count(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
 
-    /*
-        c5_speed INTEGER,
-        pan INTEGER,
-        volume INTEGER,
-        global_vol INTEGER,
-        stereo INTEGER,
-        sample_bits INTEGER,
-        relative_tone INTEGER,
-        fine_tune INTEGER,
-        vibrato_type INTEGER,
-        vibrato_sweep INTEGER,
-        vibrato_depth INTEGER,
-        vibrato_rate INTEGER,
-    */
+    // Prints a one-line count and, if any errors were recorded, writes the full list to
+    // modland_hash_errors.log next to the executable.
+    fn finish(&self) {
+        let entries = self.entries.lock().unwrap();
 
-    conn.execute(
-        "CREATE TABLE samples (
-        hash_id TEXT, 
-        song_id INTEGER, 
-        song_sample_id INTEGER,
-        text TEXT NOT NULL, 
-        length_bytes INTEGER,
-        length INTEGER,
-        FOREIGN KEY (song_id) REFERENCES files(song_id)
-        )",
-        [],
-    )?;
+        if entries.is_empty() {
+            return;
+        }
 
-    conn.execute(
-        "CREATE TABLE instruments (
-        hash_id TEXT, 
-        song_id INTEGER, 
-        text TEXT, 
-        FOREIGN KEY (song_id) REFERENCES files(song_id)
-        )",
-        [],
-    )?;
+        let log_path = Path::new(&get_db_filename()).with_file_name("modland_hash_errors.log");
 
-    conn.execute("BEGIN TRANSACTION", [])?;
+        println!(
+            "{} file(s) failed during this run, see {} for details",
+            entries.len(),
+            log_path.display()
+        );
 
-    // Listen for commands
-    for command in rx {
-        match command {
-            DbCommand::Insert(cmd) => {
-                conn.execute(&cmd, [])?;
+        if let Ok(mut file) = File::create(&log_path) {
+            for entry in entries.iter() {
+                let _ = writeln!(file, "{}", entry);
             }
-            DbCommand::Quit => break,
         }
     }
-
-    conn.execute("COMMIT", [])?;
-    conn.execute("CREATE INDEX hash_files ON files (hash_id)", [])?;
-    conn.execute("CREATE INDEX pattern_files ON files (pattern_hash)", [])?;
-    conn.execute("CREATE INDEX hash_samples ON samples (hash_id)", [])?;
-    conn.execute("CREATE INDEX length_samples ON samples (length)", [])?;
-    conn.execute("CREATE INDEX song_id_samples ON samples (song_id)", [])?;
-
-    Ok(())
 }
 
-fn build_database(out_filename: &str, database_path: &str, args: &Args) {
-    // Channel for sending commands to the database thread
-    let (tx, rx): (Sender<DbCommand>, Receiver<DbCommand>) = mpsc::channel();
+// --sample-name-class's heuristic: weighs printable-character ratio, word count and a list of
+// common greeting/credit keywords to guess whether a "sample name" field actually holds a tracker
+// greeting or credit message instead of a real sample name. Neither signal is reliable alone (a
+// short greeting like "hi" looks exactly like a name, a long creative sample name can look like a
+// sentence), so a keyword hit is decisive on its own but the length-based signal requires both a
+// high printable ratio and enough words to be a plausible message.
+fn looks_like_message(text: &str) -> bool {
+    const GREETING_KEYWORDS: &[&str] = &[
+        "greetings",
+        "greets",
+        "shout",
+        "thanks to",
+        "thanx",
+        "ripped by",
+        "tracked by",
+        "composed by",
+        "made by",
+        "written by",
+        "remix by",
+        "original by",
+        "www.",
+        "http://",
+        "https://",
+        "copyright",
+        "(c)",
+        "all rights reserved",
+    ];
+
+    let lower = text.to_ascii_lowercase();
+
+    if GREETING_KEYWORDS.iter().any(|kw| lower.contains(kw)) {
+        return true;
+    }
 
-    let filename = out_filename.to_owned();
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return false;
+    }
 
-    // Spawn the database thread
-    let db_thread = std::thread::spawn(move || {
-        run_build_db_thread(filename, rx).unwrap();
-    });
+    let char_count = trimmed.chars().count();
+    let printable = trimmed.chars().filter(|c| c.is_ascii_graphic() || *c == ' ').count();
+    let printable_ratio = printable as f64 / char_count as f64;
+    let word_count = trimmed.split_whitespace().count();
 
-    let files = get_files(database_path, args.recursive);
+    printable_ratio > 0.9 && word_count >= 4
+}
 
-    let spinner_style =
-        ProgressStyle::with_template("{prefix:.bold.dim} {wide_bar} {pos}/{len}").unwrap();
+fn sample_name_class_allows(class: &str, text: &str) -> bool {
+    match class {
+        "message" => looks_like_message(text),
+        "name" => !looks_like_message(text),
+        _ => true,
+    }
+}
 
-    let pb = ProgressBar::new(files.len() as _);
-    pb.set_style(spinner_style);
+struct Filters {
+    include_paths: Vec<String>,
+    include_file_extensions: Vec<String>,
+    exclude_paths: Vec<String>,
+    exclude_file_extensions: Vec<String>,
+    include_path_regex: Option<Regex>,
+    exclude_path_regex: Option<Regex>,
+    sample_search: Option<Regex>,
+    instrument_search: Option<Regex>,
+    search_filename: Option<Regex>,
+    sample_name_class: String,
+    strict_path_matching: bool,
+    channels: Option<u32>,
+    formats: Vec<String>,
+}
 
-    pb.set_prefix("Building database");
+impl Filters {
+    // Case-folds and unifies path separators (`\` -> `/`) so paths stored with different case or
+    // Windows-style separators still match, unless --strict-path-matching asks for exact comparison.
+    fn normalize_path(path: &str, strict: bool) -> String {
+        if strict {
+            path.to_string()
+        } else {
+            path.to_ascii_lowercase().replace('\\', "/")
+        }
+    }
 
-    files.par_iter().enumerate().for_each(|(index, input_path)| {
-        let mut track = get_track_info(input_path, args.dump_patterns);
-        track.filename = input_path.replace(database_path, "");
+    fn init_filter(filter: &str, prefix: &str) -> Vec<String> {
+        if filter.is_empty() {
+            return Vec::new();
+        }
 
-        let t = track.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
-        let pattern_hash = if t != 0 {
-            format!("{}", t)
-        } else {
-            "NULL".to_string()
-        };
+        let mut output = Vec::new();
 
-        let insert = format!("INSERT INTO files (song_id, hash_id, pattern_hash, url) VALUES ({}, '{}', {}, '{}')", 
-                index,
-                &track.sha256_hash,
-                pattern_hash,
-                get_stored_url(&track.filename));
+        for t in filter.split(',') {
+            output.push(format!("{}{}", prefix, t));
+        }
 
-        tx.send(DbCommand::Insert(insert)).expect("Failed to send command");
+        output
+    }
 
-        for sample in &track.samples {
-            let insert = format!("INSERT INTO samples (hash_id, song_id, song_sample_id, text, length_bytes, length) VALUES ({}, {}, {}, {}, {}, {})", 
-                &sample.sha256_hash,
-                index,
-                sample.sample_id,
-                &sample.text,
-                sample.length_bytes,
-                sample.length);
+    // Compiles a path filter pattern as regex, except that a pattern using none of the regex
+    // metacharacters other than `*`/`?` is treated as a simple glob (e.g. "coop/*") and has
+    // those two translated to their regex equivalents first, so users don't need regex syntax
+    // for the common case of a plain wildcard path.
+    fn compile_path_pattern(pattern: &str) -> Regex {
+        const REGEX_METACHARS: &str = ".+()[]{}|^$\\";
 
-            tx.send(DbCommand::Insert(insert)).expect("Failed to send command");
+        if pattern.contains(|c| REGEX_METACHARS.contains(c)) {
+            Regex::new(pattern).unwrap()
+        } else {
+            let translated = pattern.replace('*', ".*").replace('?', ".");
+            Regex::new(&translated).unwrap()
         }
+    }
 
-        pb.inc(1);
-    });
-
-    println!("Writing database...");
+    fn new(args: &Args) -> Filters {
+        let strict = args.strict_path_matching;
 
-    tx.send(DbCommand::Quit).expect("Failed to send command");
-    db_thread.join().unwrap();
+        let sample_search = if !args.include_sample_name.is_empty() {
+            Some(Regex::new(&args.include_sample_name.to_ascii_lowercase()).unwrap())
+        } else {
+            None
+        };
 
-    println!("Done");
-}
+        let instrument_search = if !args.include_instrument_name.is_empty() {
+            Some(Regex::new(&args.include_instrument_name.to_ascii_lowercase()).unwrap())
+        } else {
+            None
+        };
 
-fn create_db_file(filename: &str) -> Result<File> {
-    if let Ok(file) = File::create(filename) {
-        return Ok(file);
-    }
+        let search_filename = if !args.search_filename.is_empty() {
+            Some(Regex::new(&args.search_filename.to_ascii_lowercase()).unwrap())
+        } else {
+            None
+        };
 
-    bail!(
-        "Tried to create database at {} but was unable to do so. Manually download {} and place it next to the modland_has executable",
-        filename, DB_REMOTE,
-    )
-}
+        let include_path_regex = if !args.include_path_regex.is_empty() {
+            Some(Self::compile_path_pattern(&Self::normalize_path(
+                &args.include_path_regex,
+                strict,
+            )))
+        } else {
+            None
+        };
 
-fn create_progress_bar(len: usize) -> ProgressBar {
-    let pb = ProgressBar::new(len as _);
-    //pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{prefix} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})",
-        )
+        let exclude_path_regex = if !args.exclude_path_regex.is_empty() {
+            Some(Self::compile_path_pattern(&Self::normalize_path(
+                &args.exclude_path_regex,
+                strict,
+            )))
+        } else {
+            None
+        };
+
+        Filters {
+            include_paths: Self::init_filter(&Self::normalize_path(&args.include_paths, strict), ""),
+            include_file_extensions: Self::init_filter(
+                &Self::normalize_path(&args.include_file_extensions, strict),
+                ".",
+            ),
+            exclude_paths: Self::init_filter(&Self::normalize_path(&args.exclude_paths, strict), ""),
+            exclude_file_extensions: Self::init_filter(
+                &Self::normalize_path(&args.exclude_file_extensions, strict),
+                ".",
+            ),
+            include_path_regex,
+            exclude_path_regex,
+            sample_search,
+            instrument_search,
+            search_filename,
+            sample_name_class: args.sample_name_class.clone(),
+            strict_path_matching: strict,
+            channels: args.channels,
+            formats: Self::init_filter(&args.format.to_ascii_lowercase(), ""),
+        }
+    }
+
+    fn starts_with(filename: &str, tests: &[String], default_val: bool) -> bool {
+        if tests.is_empty() {
+            default_val
+        } else {
+            tests.iter().any(|t| filename.starts_with(t))
+        }
+    }
+
+    fn ends_with(filename: &str, tests: &[String], default_val: bool) -> bool {
+        if tests.is_empty() {
+            default_val
+        } else {
+            tests.iter().any(|t| filename.ends_with(t))
+        }
+    }
+
+    // The subset of the filters that only need the path itself (no parsed track info), so it can
+    // be applied during scanning in get_files() as well as here, against a path the rest of these
+    // filters can't see yet (channels/format aren't known until the file's been hashed).
+    fn matches_path(&self, filename: &str) -> bool {
+        let filename = Self::normalize_path(filename, self.strict_path_matching);
+
+        !Self::starts_with(&filename, &self.exclude_paths, false)
+            && !Self::ends_with(&filename, &self.exclude_file_extensions, false)
+            && Self::starts_with(&filename, &self.include_paths, true)
+            && Self::ends_with(&filename, &self.include_file_extensions, true)
+            && !self
+                .exclude_path_regex
+                .as_ref()
+                .is_some_and(|re| re.is_match(&filename))
+            && self
+                .include_path_regex
+                .as_ref()
+                .is_none_or(|re| re.is_match(&filename))
+    }
+
+    // Apply all the filters
+    fn apply_filter(&self, input: &[DatabaseMeta], skip_level: usize) -> Vec<DatabaseMeta> {
+        let mut output: Vec<DatabaseMeta> = Vec::new();
+
+        for i in input {
+            if self.matches_path(&i.filename)
+                && self.channels.is_none_or(|c| c == i.channel_count)
+                && (self.formats.is_empty() || self.formats.contains(&i.format.to_ascii_lowercase()))
+            {
+                output.push(i.clone());
+            }
+        }
+
+        if let Some(re) = self.search_filename.as_ref() {
+            let mut found_filename = false;
+
+            for file in &output {
+                if re.is_match(&file.filename.to_ascii_lowercase()) {
+                    found_filename = true;
+                    break;
+                }
+            }
+
+            if !found_filename {
+                return Vec::new();
+            }
+        }
+
+        if let Some(re) = self.sample_search.as_ref() {
+            for file in &output {
+                for sample in &file.samples {
+                    if re.is_match(&sample.to_ascii_lowercase())
+                        && sample_name_class_allows(&self.sample_name_class, sample)
+                    {
+                        if output.len() >= skip_level {
+                            return output;
+                        } else {
+                            return Vec::new();
+                        }
+                    }
+                }
+            }
+
+            return Vec::new();
+        }
+
+        if let Some(re) = self.instrument_search.as_ref() {
+            for file in &output {
+                for instrument in &file.instrument_names {
+                    if re.is_match(&instrument.to_ascii_lowercase()) {
+                        if output.len() >= skip_level {
+                            return output;
+                        } else {
+                            return Vec::new();
+                        }
+                    }
+                }
+            }
+
+            return Vec::new();
+        }
+
+        if output.len() >= skip_level {
+            output
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// Get files for a given directory
+// Reads a newline-separated file list from a file, or from stdin if `path` is "-".
+fn read_files_from_list(path: &str) -> Result<Vec<String>> {
+    let content = if path == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf)?;
+        buf
+    } else {
+        std::fs::read_to_string(path)?
+    };
+
+    Ok(content
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.to_owned())
+        .collect())
+}
+
+// Compiles an --ignore-patterns entry as a case-insensitive glob matched against a basename:
+// unlike Filters::compile_path_pattern (which expects either a plain glob or a full regex, never
+// both), extension-style ignore patterns routinely mix the two (e.g. "*.txt"), so every regex
+// metacharacter besides `*`/`?` is escaped here rather than only skipping translation when one's
+// present.
+fn compile_ignore_glob(pattern: &str) -> Regex {
+    let mut escaped = regex::escape(&pattern.to_ascii_lowercase());
+    escaped = escaped.replace("\\*", ".*").replace("\\?", ".");
+    Regex::new(&format!("^{}$", escaped)).unwrap()
+}
+
+// Windows refuses to open paths longer than MAX_PATH (260 chars) or named after a reserved
+// device (aux, con, nul, com1..9, lpt1..9, with or without an extension) through its normal
+// DOS-style path parser. Prefixing with `\\?\` (or `\\?\UNC\` for a `\\server\share` path) routes
+// the open straight to the NT parser instead, which has neither limit. Applied right before the
+// actual OS call rather than to every path string we carry around, so filenames we print or
+// store in the database stay exactly as the user passed them in.
+#[cfg(windows)]
+fn long_path(path: &Path) -> std::path::PathBuf {
+    let raw = path.to_string_lossy();
+    if raw.starts_with(r"\\?\") {
+        return path.to_path_buf();
+    }
+
+    let absolute = if path.is_absolute() {
+        raw.into_owned()
+    } else {
+        match std::env::current_dir() {
+            Ok(cwd) => cwd.join(path).to_string_lossy().into_owned(),
+            Err(_) => return path.to_path_buf(),
+        }
+    };
+
+    match absolute.strip_prefix(r"\\") {
+        Some(unc) => std::path::PathBuf::from(format!(r"\\?\UNC\{}", unc)),
+        None => std::path::PathBuf::from(format!(r"\\?\{}", absolute)),
+    }
+}
+
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> std::path::PathBuf {
+    path.to_path_buf()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn get_files(
+    path: &str,
+    recurse: bool,
+    errors: &RunErrors,
+    fail_fast: bool,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    files_from: &str,
+    progress_json: bool,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    skip_hidden: bool,
+    ignore_patterns: &str,
+    path_filters: &Filters,
+) -> Vec<String> {
+    let ignore_patterns: Vec<Regex> = ignore_patterns
+        .split(',')
+        .filter(|p| !p.is_empty())
+        .map(compile_ignore_glob)
+        .collect();
+    if !files_from.is_empty() {
+        return match read_files_from_list(files_from) {
+            Ok(files) => files,
+            Err(err) => {
+                if fail_fast {
+                    panic!("Failed to read --files-from list \"{}\": {}", files_from, err);
+                }
+                errors.record(files_from, &err);
+                Vec::new()
+            }
+        };
+    }
+
+    if !long_path(Path::new(path)).exists() {
+        println!(
+            "Path/File \"{}\" doesn't exist. No file(s) will be processed.",
+            path
+        );
+        return Vec::new();
+    }
+
+    // Check if "path" is a single file
+    let md = std::fs::metadata(long_path(Path::new(path))).unwrap();
+
+    if md.is_file() {
+        if min_size.is_none_or(|min| md.len() >= min)
+            && max_size.is_none_or(|max| md.len() <= max)
+            && path_filters.matches_path(path)
+        {
+            return vec![path.to_owned()];
+        }
+        return Vec::new();
+    }
+
+    let start_time = std::time::Instant::now();
+
+    let spinner_style = ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}")
         .unwrap()
-        .with_key(
-            "eta",
-            |state: &ProgressState, w: &mut dyn std::fmt::Write| {
-                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
-            },
-        )
-        .progress_chars("#>-"),
+        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ");
+
+    let pb = ProgressBar::new(0);
+    if progress_json {
+        pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    } else {
+        pb.set_style(spinner_style);
+        pb.set_prefix(format!("Fetching list of files... [{}/?]", 0));
+    }
+
+    let scanned = std::cell::Cell::new(0usize);
+    // --max-depth is user-facing "how many sub-directory levels", so it's one more than WalkDir's
+    // own depth count (which treats the scanned directory itself as depth 0).
+    let max_depth = match max_depth {
+        Some(n) => n.saturating_add(1),
+        None if !recurse => 1,
+        None => usize::MAX,
+    };
+
+    let files: Vec<String> = WalkDir::new(path)
+        .max_depth(max_depth)
+        .follow_links(follow_symlinks)
+        .into_iter()
+        .filter_entry(|e| {
+            !skip_hidden || e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.')
+        })
+        .filter_map(|e| {
+            let file = match e {
+                Ok(file) => file,
+                Err(err) => {
+                    if fail_fast {
+                        panic!("Failed to read directory entry under \"{}\": {}", path, err);
+                    }
+                    errors.record(path, &err);
+                    return None;
+                }
+            };
+
+            let metadata = match file.metadata() {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    if fail_fast {
+                        panic!(
+                            "Failed to read metadata for \"{}\": {}",
+                            file.path().display(),
+                            err
+                        );
+                    }
+                    errors.record(&file.path().to_string_lossy(), &err);
+                    return None;
+                }
+            };
+
+            let Some(filename) = file.path().to_str() else {
+                if fail_fast {
+                    panic!("Non-UTF-8 path: {}", file.path().display());
+                }
+                errors.record(&file.path().to_string_lossy(), "non-UTF-8 path, skipped");
+                return None;
+            };
+
+            let basename_lower = basename(filename).to_ascii_lowercase();
+
+            if metadata.is_file()
+                && !filename.contains("modland_hash")
+                && !ignore_patterns.iter().any(|p| p.is_match(&basename_lower))
+                && min_size.is_none_or(|min| metadata.len() >= min)
+                && max_size.is_none_or(|max| metadata.len() <= max)
+                && path_filters.matches_path(filename)
+            {
+                scanned.set(scanned.get() + 1);
+                if progress_json {
+                    emit_json_progress("scanning", filename, scanned.get(), 0);
+                } else {
+                    pb.set_message(filename.to_owned());
+                }
+                return Some(filename.to_owned());
+            }
+
+            if metadata.is_file() {
+                log::debug!("Skipping \"{}\" (size/extension filters)", filename);
+            }
+
+            None
+        })
+        .collect();
+
+    let elapsed = start_time.elapsed();
+    log::info!(
+        "Found {} file(s) under \"{}\" in {:.2}s",
+        files.len(),
+        path,
+        elapsed.as_secs_f64()
     );
-    pb
+
+    files
 }
 
-// Download and upack the database
-fn download_db() -> Result<ProgressBar> {
-    let filename = format!("{}.7z", get_db_filename());
-    let mut file = create_db_file(&filename)?;
+fn get_url(filename: &str) -> String {
+    let filename = filename.replace(' ', "%20");
+    let filename = filename.replace('\'', "%27");
+    let prefix = URL_PREFIX.get().map(String::as_str).unwrap_or("https://ftp.modland.com");
+    format!("{}{}", prefix, filename)
+}
 
-    let resp = ureq::get(DB_REMOTE).call()?;
-    let len: usize = resp.header("Content-Length").unwrap().parse()?;
+fn get_stored_url(filename: &str) -> String {
+    let filename = filename.replace(' ', "%20");
+    filename.replace('\'', "%27")
+}
 
-    let mut temp_buffer: [u8; 1024] = [0; 1024];
-    let mut reader = resp.into_reader();
+// Strips database_path (or --path-prefix-strip, when given) from a scanned file's path before
+// it's stored as files.url, then prepends --path-prefix-add. Separators are normalized to '/'
+// first so a database built on Windows (backslash paths) and one built on Linux (forward-slash
+// paths) produce identical urls for the same logical path, and strip_prefix is used instead of
+// the naive replace() this used to be so a scan root that happens to recur elsewhere in the path
+// (e.g. "/home/mods/mods/foo.mod" scanned from "/home/mods") isn't stripped twice.
+fn normalize_stored_path(input_path: &str, database_path: &str, args: &Args) -> String {
+    let normalized = input_path.replace('\\', "/");
+    let strip_prefix = match args.path_prefix_strip.as_ref() {
+        Some(prefix) => prefix.replace('\\', "/"),
+        None => database_path.replace('\\', "/"),
+    };
 
-    let pb = create_progress_bar(len);
+    let stripped = normalized.strip_prefix(strip_prefix.as_str()).unwrap_or(&normalized);
+    format!("{}{}", args.path_prefix_add, stripped)
+}
 
-    pb.set_prefix("Downloading Database");
 
-    let mut pos = 0;
+// Finds the companion file for formats that split sample data across two files (e.g. MDX/PDX),
+// looking next to `filename` for a same-stem file with the paired extension (case-insensitive).
+fn find_companion_file(filename: &str) -> Option<PathBuf> {
+    let path = Path::new(filename);
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
 
-    loop {
-        let read_size = reader.read(&mut temp_buffer)?;
+    let companion_ext = COMPANION_EXTENSIONS.iter().find_map(|(a, b)| {
+        if ext == *a {
+            Some(*b)
+        } else if ext == *b {
+            Some(*a)
+        } else {
+            None
+        }
+    })?;
+
+    let dir = path.parent()?;
+    let stem = path.file_stem()?.to_str()?;
+
+    for entry in std::fs::read_dir(dir).ok()? {
+        let entry = entry.ok()?;
+        let candidate = entry.path();
+
+        let candidate_stem = candidate.file_stem()?.to_str()?;
+        let candidate_ext = candidate.extension()?.to_str()?.to_ascii_lowercase();
+
+        if candidate_stem.eq_ignore_ascii_case(stem) && candidate_ext == companion_ext {
+            return Some(candidate);
+        }
+    }
+
+    None
+}
+
+// Hashes a module and its companion file (see find_companion_file) together as a single unit,
+// since the two only make sense as a pair.
+fn compute_pair_hash(filename: &str, companion: &Path) -> Result<String> {
+    let mut data = std::fs::read(long_path(Path::new(filename)))?;
+    let mut companion_data = std::fs::read(long_path(companion))?;
+    data.append(&mut companion_data);
+
+    let hash = sha2::Sha256::digest(&data);
+    Ok(format!("{:x}", hash))
+}
+
+// Fetches info for a track/song
+#[allow(clippy::too_many_arguments)]
+fn get_track_info(
+    filename: &str,
+    dump_patterns: bool,
+    skip_patterns: bool,
+    skip_sample_hashes: bool,
+    render_fingerprint_seconds: Option<i32>,
+    sample_charset: &str,
+    errors: &RunErrors,
+    fail_fast: bool,
+) -> TrackInfo {
+    // Calculate sha256 of the file
+    let file_data = match std::fs::read(long_path(Path::new(filename))) {
+        Ok(data) => data,
+        Err(err) => {
+            if fail_fast {
+                panic!("Failed to read \"{}\": {}", filename, err);
+            }
+            errors.record(filename, &err);
+            return placeholder_track_info(filename, "unreadable");
+        }
+    };
+    let hash = sha2::Sha256::digest(&file_data);
+    let dump_patterns_flag = dump_patterns;
+    let dump_patterns = if dump_patterns { 1 } else { 0 };
+
+    // --backend forces a single backend: "sid" skips the libopenmpt attempt entirely rather than
+    // trying it first and falling back, "libopenmpt" skips the SID fallback below. "auto" (the
+    // default, BACKEND_OVERRIDE unset) tries libopenmpt then falls back to SID, as before.
+    let try_libopenmpt = BACKEND_OVERRIDE.get().map(String::as_str) != Some("sid");
+    let try_sid = BACKEND_OVERRIDE.get().map(String::as_str) != Some("libopenmpt");
+
+    let song_data = if try_libopenmpt {
+        unsafe {
+            hash_file(
+                file_data.as_ptr(),
+                file_data.len() as _,
+                dump_patterns,
+                skip_patterns as i32,
+                skip_sample_hashes as i32,
+            )
+        }
+    } else {
+        std::ptr::null_mut()
+    };
+    let parse_error = if song_data.is_null() { get_native_parse_error() } else { None };
+
+    let mut track_info = TrackInfo {
+        filename: filename.to_owned(),
+        sha256_hash: format!("{:x}", hash),
+        file_size: file_data.len() as u64,
+        parse_error,
+        ..Default::default()
+    };
+
+    let mut sid_parsed = false;
+
+    if song_data.is_null() && try_sid {
+        if let Some(sid) = parse_sid_header(&file_data) {
+            sid_parsed = true;
+            track_info.parse_error = None;
+            // Use the first 8 bytes of the data-section hash as the pattern_hash, so a SID
+            // that's only been retitled (same music data, different header text) still lands
+            // in the same duplicate group as the original.
+            let hash_bytes: [u8; 8] = sid.data_hash[..8].try_into().unwrap();
+            track_info.pattern_hash = u64::from_be_bytes(hash_bytes);
+            track_info.instrument_names = vec![
+                format!("title: {}", sid.title),
+                format!("author: {}", sid.author),
+                format!("released: {}", sid.released),
+                format!("songs: {} (default {})", sid.songs, sid.start_song),
+            ];
+
+            if dump_patterns_flag {
+                for name in &track_info.instrument_names {
+                    println!("{}", name);
+                }
+            }
+        }
+    }
+
+    if song_data.is_null() && needs_uade_backend(filename) {
+        log::debug!(
+            "\"{}\" needs a UADE backend for pattern/sample data, falling back to sha256-only matching",
+            filename
+        );
+    }
+
+    if !song_data.is_null() {
+        let hash_id = unsafe { (*song_data).hash };
+        let samples = unsafe { (*song_data).get_samples() };
+        track_info.pattern_hash = hash_id;
+        track_info.channel_count = unsafe { (*song_data).channel_count };
+        track_info.sample_count = unsafe { (*song_data).sample_count };
+        track_info.instrument_count = unsafe { (*song_data).instrument_count };
+        track_info.normalized_pattern_hash = unsafe { (*song_data).normalized_pattern_hash };
+        track_info.canonical_hash = unsafe { (*song_data).canonical_hash };
+        track_info.is_empty_pattern = unsafe { (*song_data).is_empty_pattern } != 0;
+        track_info.warnings = unsafe { (*song_data).get_warnings() };
+        track_info.message = unsafe { (*song_data).get_message() };
+
+        for sample in samples {
+            let (sha256_hash, fingerprint) = if skip_sample_hashes {
+                ("NULL".to_string(), None)
+            } else if let Some(data) = sample.get_data() {
+                let hash = sha2::Sha256::digest(data);
+                let fingerprint = compute_sample_fingerprint(data, sample.bits_per_sample);
+                (format!("'{:x}'", hash), Some(fingerprint))
+            } else {
+                ("NULL".to_string(), None)
+            };
+
+            track_info.samples.push(SampleInfo {
+                sample_id: sample.sample_id,
+                sha256_hash,
+                fingerprint,
+                text: sample.get_text(sample_charset),
+                length_bytes: sample.length_bytes as _,
+                length: sample.length as _,
+                c5_speed: sample.c5_speed,
+                pan: sample.pan,
+                volume: sample.volume,
+                global_vol: sample.global_vol,
+                stereo: sample.stereo,
+                bits_per_sample: sample.bits_per_sample,
+                relative_tone: sample.relative_tone,
+                fine_tune: sample.fine_tune,
+                vib_type: sample.vib_type,
+                vib_sweep: sample.vib_sweep,
+                vib_depth: sample.vib_depth,
+                vib_rate: sample.vib_rate,
+            });
+        }
+
+        let instrument_names = unsafe { (*song_data).get_instrument_names(sample_charset) };
+
+        for name in instrument_names {
+            track_info.instrument_names.push(name);
+        }
+
+        //let sample_names = unsafe { get_string_cstr((*song_data).sample_names) };
+        //track_info.sample_names = sample_names;
+        //track_info.pattern_hash = hash_id;
+
+        if dump_patterns_flag {
+            track_info.pattern_text = unsafe { (*song_data).get_pattern_text() };
+        }
+
+        unsafe { free_hash_data(song_data) };
+    }
+
+    if track_info.pattern_hash == 0 || track_info.is_empty_pattern {
+        if let Some(seconds) = render_fingerprint_seconds {
+            let render_hash =
+                unsafe { render_fingerprint(file_data.as_ptr(), file_data.len() as _, seconds) };
+
+            if render_hash != 0 {
+                track_info.render_hash = Some(render_hash);
+            }
+        }
+    }
+
+    if let Some(companion) = find_companion_file(filename) {
+        if let Ok(pair_hash) = compute_pair_hash(filename, &companion) {
+            track_info.companion_url = Some(companion.to_string_lossy().into_owned());
+            track_info.pair_hash = Some(pair_hash);
+        }
+    }
+
+    track_info.parse_status = get_parse_status(filename, !song_data.is_null() || sid_parsed).to_owned();
+    track_info.format = get_format_extension(filename);
+    // Which of the paths above actually produced the hash, see Args::backend. "none" covers
+    // formats needing a backend we don't have (UADE-only, or a corrupt/unsupported file), where
+    // only the whole-file sha256 is usable for matching.
+    track_info.backend = if !song_data.is_null() {
+        "libopenmpt".to_string()
+    } else if sid_parsed {
+        "sid".to_string()
+    } else {
+        "none".to_string()
+    };
+
+    track_info
+}
+
+// Field/list separators for the worker IPC protocol below. Chosen from the control-character
+// range since none of the encoded values (hashes, filenames, sample text) can legally contain them.
+const WORKER_FIELD_SEP: char = '\u{1}';
+const WORKER_LIST_SEP: char = '\u{2}';
+const WORKER_SAMPLE_FIELD_SEP: char = '\u{3}';
+
+// Encodes a TrackInfo as a single line of text so it can be sent back from a --parse-worker
+// child process over stdout. filename is left out since the parent already knows it.
+fn encode_track_info(track: &TrackInfo) -> String {
+    let render_hash = track.render_hash.map(|h| h.to_string()).unwrap_or_default();
+    let companion_url = track.companion_url.clone().unwrap_or_default();
+    let pair_hash = track.pair_hash.clone().unwrap_or_default();
+
+    let samples = track
+        .samples
+        .iter()
+        .map(|s| {
+            let (fingerprint_head_hash, fingerprint_tail_hash, fingerprint_rms) =
+                match s.fingerprint.as_ref() {
+                    Some(f) => (f.head_hash.clone(), f.tail_hash.clone(), f.rms.to_string()),
+                    None => (String::new(), String::new(), String::new()),
+                };
+
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}",
+                s.sample_id,
+                s.sha256_hash,
+                s.text,
+                s.length_bytes,
+                s.length,
+                s.c5_speed,
+                s.pan,
+                s.volume,
+                s.global_vol,
+                s.stereo,
+                s.bits_per_sample,
+                s.relative_tone,
+                s.fine_tune,
+                s.vib_type,
+                s.vib_sweep,
+                s.vib_depth,
+                s.vib_rate,
+                fingerprint_head_hash,
+                fingerprint_tail_hash,
+                fingerprint_rms,
+                sep = WORKER_SAMPLE_FIELD_SEP
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(&WORKER_LIST_SEP.to_string());
+
+    let instruments = track.instrument_names.join(&WORKER_LIST_SEP.to_string());
+
+    let parse_error = track.parse_error.clone().unwrap_or_default();
+    let warnings = track.warnings.clone().unwrap_or_default();
+    let message = track.message.clone().unwrap_or_default();
+
+    format!(
+        "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}",
+        track.pattern_hash,
+        track.sha256_hash,
+        render_hash,
+        companion_url,
+        pair_hash,
+        track.parse_status,
+        track.format,
+        track.channel_count,
+        track.sample_count,
+        track.instrument_count,
+        track.normalized_pattern_hash,
+        track.file_size,
+        samples,
+        instruments,
+        track.canonical_hash,
+        track.is_empty_pattern as u8,
+        parse_error,
+        track.backend,
+        warnings,
+        message,
+        sep = WORKER_FIELD_SEP
+    )
+}
+
+// Inverse of encode_track_info(). Returns None if `line` isn't a well-formed encoding, which the
+// caller treats the same as a worker crash (i.e. the file is recorded as corrupt and skipped).
+fn decode_track_info(filename: &str, line: &str) -> Option<TrackInfo> {
+    let mut fields = line.split(WORKER_FIELD_SEP);
+
+    let pattern_hash: u64 = fields.next()?.parse().ok()?;
+    let sha256_hash = fields.next()?.to_owned();
+    let render_hash = fields.next()?;
+    let companion_url = fields.next()?;
+    let pair_hash = fields.next()?;
+    let parse_status = fields.next()?.to_owned();
+    let format = fields.next()?.to_owned();
+    let channel_count: u32 = fields.next()?.parse().ok()?;
+    let sample_count: u32 = fields.next()?.parse().ok()?;
+    let instrument_count: u32 = fields.next()?.parse().ok()?;
+    let normalized_pattern_hash: u64 = fields.next()?.parse().ok()?;
+    let file_size: u64 = fields.next()?.parse().ok()?;
+    let samples_field = fields.next()?;
+    let instruments_field = fields.next()?;
+    let canonical_hash: u64 = fields.next()?.parse().ok()?;
+    let is_empty_pattern: u8 = fields.next()?.parse().ok()?;
+    let parse_error = fields.next()?.to_owned();
+    let backend = fields.next()?.to_owned();
+    let warnings = fields.next()?.to_owned();
+    let message = fields.next()?.to_owned();
+
+    let samples = if samples_field.is_empty() {
+        Vec::new()
+    } else {
+        samples_field
+            .split(WORKER_LIST_SEP)
+            .filter_map(|s| {
+                let mut f = s.split(WORKER_SAMPLE_FIELD_SEP);
+                let sample_id = f.next()?.parse().ok()?;
+                let sha256_hash = f.next()?.to_owned();
+                let text = f.next()?.to_owned();
+                let length_bytes = f.next()?.parse().ok()?;
+                let length = f.next()?.parse().ok()?;
+                let c5_speed = f.next()?.parse().ok()?;
+                let pan = f.next()?.parse().ok()?;
+                let volume = f.next()?.parse().ok()?;
+                let global_vol = f.next()?.parse().ok()?;
+                let stereo = f.next()?.parse().ok()?;
+                let bits_per_sample = f.next()?.parse().ok()?;
+                let relative_tone = f.next()?.parse().ok()?;
+                let fine_tune = f.next()?.parse().ok()?;
+                let vib_type = f.next()?.parse().ok()?;
+                let vib_sweep = f.next()?.parse().ok()?;
+                let vib_depth = f.next()?.parse().ok()?;
+                let vib_rate = f.next()?.parse().ok()?;
+                let fingerprint_head_hash = f.next()?.to_owned();
+                let fingerprint_tail_hash = f.next()?.to_owned();
+                let fingerprint_rms = f.next()?.parse::<f64>().ok();
+                let fingerprint = match (fingerprint_rms, fingerprint_head_hash.is_empty()) {
+                    (Some(rms), false) => Some(SampleFingerprint {
+                        head_hash: fingerprint_head_hash,
+                        tail_hash: fingerprint_tail_hash,
+                        rms,
+                    }),
+                    _ => None,
+                };
+
+                Some(SampleInfo {
+                    sample_id,
+                    sha256_hash,
+                    text,
+                    length_bytes,
+                    length,
+                    c5_speed,
+                    pan,
+                    volume,
+                    global_vol,
+                    stereo,
+                    bits_per_sample,
+                    relative_tone,
+                    fine_tune,
+                    vib_type,
+                    vib_sweep,
+                    vib_depth,
+                    vib_rate,
+                    fingerprint,
+                })
+            })
+            .collect()
+    };
+
+    let instrument_names = if instruments_field.is_empty() {
+        Vec::new()
+    } else {
+        instruments_field
+            .split(WORKER_LIST_SEP)
+            .map(|s| s.to_owned())
+            .collect()
+    };
+
+    Some(TrackInfo {
+        pattern_hash,
+        sha256_hash,
+        filename: filename.to_owned(),
+        samples,
+        instrument_names,
+        render_hash: if render_hash.is_empty() {
+            None
+        } else {
+            render_hash.parse().ok()
+        },
+        companion_url: if companion_url.is_empty() {
+            None
+        } else {
+            Some(companion_url.to_owned())
+        },
+        pair_hash: if pair_hash.is_empty() {
+            None
+        } else {
+            Some(pair_hash.to_owned())
+        },
+        parse_status,
+        parse_error: if parse_error.is_empty() { None } else { Some(parse_error) },
+        warnings: if warnings.is_empty() { None } else { Some(warnings) },
+        message: if message.is_empty() { None } else { Some(message) },
+        format,
+        backend,
+        channel_count,
+        sample_count,
+        instrument_count,
+        normalized_pattern_hash,
+        file_size,
+        canonical_hash,
+        is_empty_pattern: is_empty_pattern != 0,
+        pattern_text: None,
+    })
+}
+
+// Runs as the entry point for a --parse-worker child process: parses a single file and prints
+// its TrackInfo (encoded) to stdout for the parent to read back. Kept as a thin wrapper around
+// get_track_info() so a native crash while parsing is the only thing that can go wrong here.
+fn run_parse_worker(
+    filename: &str,
+    dump_patterns: bool,
+    skip_patterns: bool,
+    skip_sample_hashes: bool,
+    render_fingerprint_seconds: Option<i32>,
+    sample_charset: &str,
+) -> Result<()> {
+    // fail_fast=true here: any read error should abort this worker process so the parent sees
+    // a non-zero exit and records the file as crashed, rather than this process trying to keep
+    // its own error log that nothing downstream would ever read.
+    let errors = RunErrors::new();
+    let track = get_track_info(
+        filename,
+        dump_patterns,
+        skip_patterns,
+        skip_sample_hashes,
+        render_fingerprint_seconds,
+        sample_charset,
+        &errors,
+        true,
+    );
+    println!("{}", encode_track_info(&track));
+    Ok(())
+}
+
+// Builds a placeholder TrackInfo for a file whose worker process didn't come back with a usable
+// result (crashed, was killed, timed out, or printed something we couldn't parse). parse_status
+// is set to the given reason rather than "corrupt" so these can be told apart from files
+// get_parse_status() classified as corrupt without the native parser ever misbehaving. The
+// sha256 is still computed directly here since hashing the raw bytes is safe Rust and doesn't
+// need isolating.
+fn placeholder_track_info(filename: &str, parse_status: &str) -> TrackInfo {
+    let data = std::fs::read(long_path(Path::new(filename))).ok();
+    let sha256_hash = data
+        .as_ref()
+        .map(|data| format!("{:x}", sha2::Sha256::digest(data)))
+        .unwrap_or_default();
+    let file_size = data.map(|data| data.len() as u64).unwrap_or(0);
+
+    TrackInfo {
+        filename: filename.to_owned(),
+        sha256_hash,
+        file_size,
+        parse_status: parse_status.to_owned(),
+        format: get_format_extension(filename),
+        ..Default::default()
+    }
+}
+
+// Caps a worker process's virtual memory so a pathological file can't exhaust system memory.
+// Only enforceable on Unix (via RLIMIT_AS); a no-op elsewhere since Windows has no equivalent
+// per-process rlimit.
+#[cfg(unix)]
+fn apply_memory_limit(command: &mut std::process::Command, limit_mb: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let limit_bytes = limit_mb.saturating_mul(1024 * 1024) as libc::rlim_t;
+
+    unsafe {
+        command.pre_exec(move || {
+            let limit = libc::rlimit {
+                rlim_cur: limit_bytes,
+                rlim_max: limit_bytes,
+            };
+
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_memory_limit(_command: &mut std::process::Command, _limit_mb: u64) {}
+
+// Polls a child process until it exits or `timeout_seconds` elapses (None waits forever, the
+// pre-timeout behaviour). Returns None on timeout; the child is left running so the caller can
+// kill() it.
+fn wait_with_timeout(
+    child: &mut std::process::Child,
+    timeout_seconds: Option<u64>,
+) -> Option<std::process::ExitStatus> {
+    let Some(timeout_seconds) = timeout_seconds else {
+        return child.wait().ok();
+    };
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout_seconds);
+
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Some(status);
+        }
+
+        if std::time::Instant::now() >= deadline {
+            return None;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+}
+
+// Same contract as get_track_info(), but runs the actual parsing in a child process (re-invoking
+// this executable with --parse-worker) so a crash, hang, or runaway allocation in libopenmpt
+// only loses the one file being parsed, rather than aborting a build that might be hours into a
+// large directory tree.
+#[allow(clippy::too_many_arguments)]
+fn get_track_info_isolated(
+    exe: &Path,
+    filename: &str,
+    dump_patterns: bool,
+    skip_patterns: bool,
+    skip_sample_hashes: bool,
+    render_fingerprint_seconds: Option<i32>,
+    sample_charset: &str,
+    timeout_seconds: Option<u64>,
+    memory_limit_mb: Option<u64>,
+) -> TrackInfo {
+    let mut command = std::process::Command::new(exe);
+    command
+        .arg("--parse-worker")
+        .arg(filename)
+        .arg("--sample-charset")
+        .arg(sample_charset)
+        .stdout(std::process::Stdio::piped());
+
+    if dump_patterns {
+        command.arg("--dump-patterns");
+    }
+
+    if skip_patterns {
+        command.arg("--samples-only");
+    }
+
+    if skip_sample_hashes {
+        command.arg("--no-sample-hashes");
+    }
+
+    if let Some(backend) = BACKEND_OVERRIDE.get() {
+        command.arg("--backend").arg(backend);
+    }
+
+    if let Some(seconds) = render_fingerprint_seconds {
+        command
+            .arg("--render-fingerprint-seconds")
+            .arg(seconds.to_string());
+    }
+
+    if let Some(limit_mb) = memory_limit_mb {
+        apply_memory_limit(&mut command, limit_mb);
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log::warn!("Failed to spawn worker process for \"{}\": {}", filename, e);
+            return placeholder_track_info(filename, "crashed");
+        }
+    };
+
+    let status = if timeout_seconds.is_none() {
+        child.wait().ok()
+    } else {
+        wait_with_timeout(&mut child, timeout_seconds)
+    };
+
+    let status = match status {
+        Some(status) => status,
+        None => {
+            log::warn!(
+                "Worker process timed out after {}s while parsing \"{}\", skipping",
+                timeout_seconds.unwrap_or_default(),
+                filename
+            );
+            let _ = child.kill();
+            let _ = child.wait();
+            return placeholder_track_info(filename, "timed_out");
+        }
+    };
+
+    let mut stdout = String::new();
+    if let Some(mut handle) = child.stdout.take() {
+        let _ = handle.read_to_string(&mut stdout);
+    }
+
+    if !status.success() {
+        log::warn!(
+            "Worker process crashed while parsing \"{}\" ({}), skipping",
+            filename,
+            status
+        );
+        return placeholder_track_info(filename, "crashed");
+    }
+
+    match decode_track_info(filename, stdout.trim_end()) {
+        Some(track) => track,
+        None => {
+            log::warn!(
+                "Worker process returned malformed output for \"{}\", skipping",
+                filename
+            );
+            placeholder_track_info(filename, "crashed")
+        }
+    }
+}
+
+// Writes raw PCM data out as a minimal canonical (uncompressed) WAV file.
+fn write_wav_file(
+    path: &Path,
+    data: &[u8],
+    channels: u16,
+    bits_per_sample: u16,
+    sample_rate: u32,
+) -> Result<()> {
+    let byte_rate = sample_rate * channels as u32 * (bits_per_sample as u32 / 8);
+    let block_align = channels * (bits_per_sample / 8);
+    let data_len = data.len() as u32;
+    let riff_len = 36 + data_len;
+
+    let mut file = File::create(path)?;
+    file.write_all(b"RIFF")?;
+    file.write_all(&riff_len.to_le_bytes())?;
+    file.write_all(b"WAVE")?;
+    file.write_all(b"fmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?; // PCM
+    file.write_all(&channels.to_le_bytes())?;
+    file.write_all(&sample_rate.to_le_bytes())?;
+    file.write_all(&byte_rate.to_le_bytes())?;
+    file.write_all(&block_align.to_le_bytes())?;
+    file.write_all(&bits_per_sample.to_le_bytes())?;
+    file.write_all(b"data")?;
+    file.write_all(&data_len.to_le_bytes())?;
+    file.write_all(data)?;
+
+    Ok(())
+}
+
+// Writes a song's normalized pattern text (see --dump-patterns-dir) to "<sha256>.txt" under
+// `dir`, named after the track's own sha256 hash so two suspected dupes' dumps can be found and
+// diffed without needing their original filenames.
+fn write_pattern_dump(dir: &str, sha256_hash: &str, text: &str) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = Path::new(dir).join(format!("{}.txt", sha256_hash));
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+// Must match the separator hash_patterns() (interface.cpp) writes between patterns.
+const PATTERN_DUMP_SEPARATOR: &str = "=======================================================\n";
+
+// Loads both files via get_track_info (forcing pattern-text generation regardless of
+// --dump-patterns) and diffs their normalized pattern text pattern-by-pattern, row-by-row, so
+// it's obvious which parts of two suspected dupes actually differ without opening a tracker.
+fn diff_patterns(file_a: &str, file_b: &str, args: &Args) -> Result<()> {
+    let errors = RunErrors::new();
+
+    let info_a = get_track_info(
+        file_a,
+        true,
+        false,
+        false,
+        args.render_fingerprint_seconds,
+        &args.sample_charset,
+        &errors,
+        args.fail_fast,
+    );
+    let info_b = get_track_info(
+        file_b,
+        true,
+        false,
+        false,
+        args.render_fingerprint_seconds,
+        &args.sample_charset,
+        &errors,
+        args.fail_fast,
+    );
+
+    errors.finish();
+
+    let text_a = info_a
+        .pattern_text
+        .ok_or_else(|| anyhow::anyhow!("\"{}\" has no pattern data to diff", file_a))?;
+    let text_b = info_b
+        .pattern_text
+        .ok_or_else(|| anyhow::anyhow!("\"{}\" has no pattern data to diff", file_b))?;
+
+    let patterns_a: Vec<&str> = text_a
+        .split(PATTERN_DUMP_SEPARATOR)
+        .filter(|p| !p.is_empty())
+        .collect();
+    let patterns_b: Vec<&str> = text_b
+        .split(PATTERN_DUMP_SEPARATOR)
+        .filter(|p| !p.is_empty())
+        .collect();
+
+    println!(
+        "{} has {} pattern(s), {} has {} pattern(s)",
+        file_a,
+        patterns_a.len(),
+        file_b,
+        patterns_b.len()
+    );
+
+    let common = patterns_a.len().min(patterns_b.len());
+    let mut differing_patterns = 0;
+
+    for (i, (pattern_a, pattern_b)) in patterns_a.iter().zip(patterns_b.iter()).enumerate().take(common) {
+        let rows_a: Vec<&str> = pattern_a.lines().collect();
+        let rows_b: Vec<&str> = pattern_b.lines().collect();
+
+        if rows_a == rows_b {
+            continue;
+        }
+
+        differing_patterns += 1;
+        println!("pattern {}:", i);
+
+        let row_count = rows_a.len().max(rows_b.len());
+        for r in 0..row_count {
+            let row_a = rows_a.get(r).copied().unwrap_or("(missing row)");
+            let row_b = rows_b.get(r).copied().unwrap_or("(missing row)");
+
+            if row_a != row_b {
+                println!("  row {}: \"{}\" != \"{}\"", r, row_a, row_b);
+            }
+        }
+    }
+
+    if patterns_a.len() != patterns_b.len() {
+        println!(
+            "pattern count differs: {} has {}, {} has {}",
+            file_a,
+            patterns_a.len(),
+            file_b,
+            patterns_b.len()
+        );
+    }
+
+    if differing_patterns == 0 && patterns_a.len() == patterns_b.len() {
+        println!("patterns are identical");
+    }
+
+    Ok(())
+}
+
+// Accumulated per-stage timings for --bench, in nanoseconds. Atomics so the single- and
+// multi-threaded passes can share the exact same measuring code.
+struct BenchTimes {
+    io_nanos: std::sync::atomic::AtomicU64,
+    sha256_nanos: std::sync::atomic::AtomicU64,
+    parse_nanos: std::sync::atomic::AtomicU64,
+    sample_hash_nanos: std::sync::atomic::AtomicU64,
+    bytes: std::sync::atomic::AtomicU64,
+}
+
+impl BenchTimes {
+    fn new() -> BenchTimes {
+        BenchTimes {
+            io_nanos: std::sync::atomic::AtomicU64::new(0),
+            sha256_nanos: std::sync::atomic::AtomicU64::new(0),
+            parse_nanos: std::sync::atomic::AtomicU64::new(0),
+            sample_hash_nanos: std::sync::atomic::AtomicU64::new(0),
+            bytes: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    fn add(&self, io: std::time::Duration, sha256: std::time::Duration, parse: std::time::Duration, sample_hash: std::time::Duration, bytes: u64) {
+        use std::sync::atomic::Ordering::Relaxed;
+        self.io_nanos.fetch_add(io.as_nanos() as u64, Relaxed);
+        self.sha256_nanos.fetch_add(sha256.as_nanos() as u64, Relaxed);
+        self.parse_nanos.fetch_add(parse.as_nanos() as u64, Relaxed);
+        self.sample_hash_nanos.fetch_add(sample_hash.as_nanos() as u64, Relaxed);
+        self.bytes.fetch_add(bytes, Relaxed);
+    }
+}
+
+// The timed unit of work for --bench: read the file (I/O), sha256 it (whole-file hash), run it
+// through libopenmpt (parse), then sha256 every sample's PCM data (sample hashing) — the same
+// four stages get_track_info performs, just with a clock around each one and nothing written
+// anywhere. Unreadable/unparseable files are silently skipped; --bench measures throughput, not
+// correctness.
+fn bench_file(filename: &str, times: &BenchTimes) {
+    let io_start = std::time::Instant::now();
+    let file_data = match std::fs::read(filename) {
+        Ok(data) => data,
+        Err(_) => return,
+    };
+    let io_time = io_start.elapsed();
+
+    let sha_start = std::time::Instant::now();
+    let _hash = sha2::Sha256::digest(&file_data);
+    let sha_time = sha_start.elapsed();
+
+    let parse_start = std::time::Instant::now();
+    let song_data = unsafe { hash_file(file_data.as_ptr(), file_data.len() as _, 0, 0, 0) };
+    let parse_time = parse_start.elapsed();
+
+    let mut sample_hash_time = std::time::Duration::ZERO;
+
+    if !song_data.is_null() {
+        let samples = unsafe { (*song_data).get_samples() };
+        let sample_start = std::time::Instant::now();
+
+        for sample in samples {
+            if let Some(data) = sample.get_data() {
+                let _hash = sha2::Sha256::digest(data);
+            }
+        }
+
+        sample_hash_time = sample_start.elapsed();
+
+        unsafe { free_hash_data(song_data) };
+    }
+
+    times.add(io_time, sha_time, parse_time, sample_hash_time, file_data.len() as u64);
+}
+
+fn run_bench_pass(files: &[String], threaded: bool) -> (BenchTimes, std::time::Duration) {
+    let times = BenchTimes::new();
+    let start = std::time::Instant::now();
+
+    if threaded {
+        files.par_iter().for_each(|filename| bench_file(filename, &times));
+    } else {
+        for filename in files {
+            bench_file(filename, &times);
+        }
+    }
+
+    (times, start.elapsed())
+}
+
+fn print_bench_report(label: &str, times: &BenchTimes, elapsed: std::time::Duration) {
+    use std::sync::atomic::Ordering::Relaxed;
+
+    let secs = elapsed.as_secs_f64();
+    let mb = times.bytes.load(Relaxed) as f64 / (1024.0 * 1024.0);
+    let mb_per_sec = if secs > 0.0 { mb / secs } else { 0.0 };
+
+    println!("== {} ==", label);
+    println!("  I/O:          {:.3}s", times.io_nanos.load(Relaxed) as f64 / 1e9);
+    println!("  sha256:       {:.3}s", times.sha256_nanos.load(Relaxed) as f64 / 1e9);
+    println!("  libopenmpt:   {:.3}s", times.parse_nanos.load(Relaxed) as f64 / 1e9);
+    println!("  sample hash:  {:.3}s", times.sample_hash_nanos.load(Relaxed) as f64 / 1e9);
+    println!("  total:        {:.3}s ({:.2} MB/s)", secs, mb_per_sec);
+}
+
+// Hashes every file under `dir` without touching the database: once single-threaded, once via
+// rayon, so the reported stage breakdown and MB/s make it obvious where a performance change
+// (faster hash, mmap I/O, ...) actually helps.
+fn run_benchmark(dir: &str, args: &Args) -> Result<()> {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        dir,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    println!("Benchmarking {} file(s)...\n", files.len());
+
+    let (single_times, single_elapsed) = run_bench_pass(&files, false);
+    print_bench_report("Single-threaded", &single_times, single_elapsed);
+
+    println!();
+
+    let (multi_times, multi_elapsed) = run_bench_pass(&files, true);
+    print_bench_report(
+        &format!("Multi-threaded ({} threads)", rayon::current_num_threads()),
+        &multi_times,
+        multi_elapsed,
+    );
+
+    errors.finish();
+
+    Ok(())
+}
+
+// Exports every sample carried by a single module file as a .wav, named after the sample's
+// id within the song. Operates on the raw CSampleData directly (rather than going through
+// get_track_info/TrackInfo) since the sample PCM data itself isn't kept around once hashed.
+fn export_samples_from_file(filename: &str, outdir: &str, dump_patterns: bool) -> Result<()> {
+    let mut file = File::open(long_path(Path::new(filename)))?;
+    let mut file_data = Vec::new();
+    file.read_to_end(&mut file_data)?;
+
+    let dump_patterns = if dump_patterns { 1 } else { 0 };
+    let song_data = unsafe { hash_file(file_data.as_ptr(), file_data.len() as _, dump_patterns, 0, 0) };
+
+    if song_data.is_null() {
+        bail!("Unable to parse \"{}\", no samples to export", filename);
+    }
+
+    let stem = Path::new(filename)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "sample".to_owned());
+
+    let samples = unsafe { (*song_data).get_samples() };
+    let mut exported = 0;
+
+    for sample in samples {
+        let data = match sample.get_data() {
+            Some(data) => data,
+            None => continue,
+        };
+
+        let channels = if sample.stereo != 0 { 2 } else { 1 };
+        let sample_rate = if sample.c5_speed != 0 {
+            sample.c5_speed
+        } else {
+            8363
+        };
+        let bits_per_sample = if sample.bits_per_sample != 0 {
+            sample.bits_per_sample as u16
+        } else {
+            8
+        };
+
+        let out_path = Path::new(outdir).join(format!("{}_{:02}.wav", stem, sample.sample_id));
+        write_wav_file(&out_path, data, channels, bits_per_sample, sample_rate)?;
+
+        println!("Wrote {}", out_path.display());
+        exported += 1;
+    }
+
+    unsafe { free_hash_data(song_data) };
+
+    if exported == 0 {
+        println!("\"{}\" has no samples with data to export", filename);
+    }
+
+    Ok(())
+}
+
+// Exports the samples of every file found under `dir` (a single file or, with --recursive, a
+// whole directory tree). DB-only songs can't be exported this way: the database stores sample
+// hashes and lengths, not the raw PCM, so only locally-present files can be re-rendered.
+fn export_samples(dir: &str, outdir: &str, args: &Args) -> Result<()> {
+    std::fs::create_dir_all(outdir)?;
+
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        dir,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    for filename in files {
+        export_samples_from_file(&filename, outdir, args.dump_patterns)?;
+    }
+
+    errors.finish();
+
+    Ok(())
+}
+
+// A column to pull out of a SQLite table for --export parquet:<dir>. Every column in
+// files/samples/instruments columns are TEXT, a 64-bit integer, or (for samples.fingerprint_rms)
+// a REAL, so that's the only distinction export_table_to_parquet() needs to make.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ParquetColumnKind {
+    Text,
+    Int,
+    Real,
+}
+
+struct ParquetColumn {
+    name: &'static str,
+    kind: ParquetColumnKind,
+}
+
+// Runs `SELECT <columns> FROM <table>`, builds one Arrow array per column (nullable, since none
+// of the three tables declare every column NOT NULL), and writes the resulting RecordBatch out
+// as a single-row-group Parquet file.
+fn export_table_to_parquet(
+    db: &Connection,
+    table: &str,
+    columns: &[ParquetColumn],
+    out_path: &Path,
+) -> Result<()> {
+    let column_list = columns.iter().map(|c| c.name).collect::<Vec<_>>().join(", ");
+    let query = format!("SELECT {} FROM {}", column_list, table);
+
+    let mut stmnt = db.prepare(&query)?;
+    let mut int_columns: Vec<Vec<Option<i64>>> = columns.iter().map(|_| Vec::new()).collect();
+    let mut text_columns: Vec<Vec<Option<String>>> = columns.iter().map(|_| Vec::new()).collect();
+    let mut real_columns: Vec<Vec<Option<f64>>> = columns.iter().map(|_| Vec::new()).collect();
+
+    let mut rows = stmnt.query([])?;
+    while let Some(row) = rows.next()? {
+        for (i, column) in columns.iter().enumerate() {
+            match column.kind {
+                ParquetColumnKind::Text => text_columns[i].push(row.get(i)?),
+                ParquetColumnKind::Int => int_columns[i].push(row.get(i)?),
+                ParquetColumnKind::Real => real_columns[i].push(row.get(i)?),
+            }
+        }
+    }
+
+    let mut fields = Vec::with_capacity(columns.len());
+    let mut arrays: Vec<arrow::array::ArrayRef> = Vec::with_capacity(columns.len());
+
+    for (i, column) in columns.iter().enumerate() {
+        match column.kind {
+            ParquetColumnKind::Text => {
+                fields.push(arrow::datatypes::Field::new(column.name, arrow::datatypes::DataType::Utf8, true));
+                arrays.push(std::sync::Arc::new(arrow::array::StringArray::from(std::mem::take(
+                    &mut text_columns[i],
+                ))));
+            }
+            ParquetColumnKind::Int => {
+                fields.push(arrow::datatypes::Field::new(column.name, arrow::datatypes::DataType::Int64, true));
+                arrays.push(std::sync::Arc::new(arrow::array::Int64Array::from(std::mem::take(
+                    &mut int_columns[i],
+                ))));
+            }
+            ParquetColumnKind::Real => {
+                fields.push(arrow::datatypes::Field::new(column.name, arrow::datatypes::DataType::Float64, true));
+                arrays.push(std::sync::Arc::new(arrow::array::Float64Array::from(std::mem::take(
+                    &mut real_columns[i],
+                ))));
+            }
+        }
+    }
+
+    let schema = std::sync::Arc::new(arrow::datatypes::Schema::new(fields));
+    let batch = arrow::record_batch::RecordBatch::try_new(schema.clone(), arrays)?;
+
+    let file = File::create(out_path)?;
+    let props = parquet::file::properties::WriterProperties::builder().build();
+    let mut writer = parquet::arrow::ArrowWriter::try_new(file, schema, Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+
+    Ok(())
+}
+
+// Dumps the database's files/samples/instruments tables as Parquet, one file per table, so the
+// modland corpus can be analyzed (sample reuse graphs, format statistics) in pandas/polars
+// without going through SQLite. `target` is "parquet:<dir>" — the scheme prefix leaves room for
+// other export kinds later without another top-level flag.
+fn export_database(target: &str, db: &Connection) -> Result<()> {
+    let Some(outdir) = target.strip_prefix("parquet:") else {
+        bail!("--export expects \"parquet:<dir>\", got \"{}\"", target);
+    };
+
+    std::fs::create_dir_all(outdir)?;
+
+    export_table_to_parquet(
+        db,
+        "files",
+        &[
+            ParquetColumn { name: "song_id", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "hash_id", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "pattern_hash", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "normalized_pattern_hash", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "canonical_hash", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "is_empty_pattern", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "render_hash", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "pair_hash", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "companion_url", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "parse_status", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "parse_error", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "warnings", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "format", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "backend", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "channel_count", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "sample_count", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "instrument_count", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "file_size", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "mtime_unix", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "pattern_text", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "url", kind: ParquetColumnKind::Text },
+        ],
+        &Path::new(outdir).join("files.parquet"),
+    )?;
+
+    export_table_to_parquet(
+        db,
+        "samples",
+        &[
+            ParquetColumn { name: "hash_id", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "song_id", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "song_sample_id", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "text", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "length_bytes", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "length", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "c5_speed", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "pan", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "volume", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "global_vol", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "stereo", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "sample_bits", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "relative_tone", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "fine_tune", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "vibrato_type", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "vibrato_sweep", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "vibrato_depth", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "vibrato_rate", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "fingerprint_head_hash", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "fingerprint_tail_hash", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "fingerprint_rms", kind: ParquetColumnKind::Real },
+        ],
+        &Path::new(outdir).join("samples.parquet"),
+    )?;
+
+    export_table_to_parquet(
+        db,
+        "instruments",
+        &[
+            ParquetColumn { name: "hash_id", kind: ParquetColumnKind::Text },
+            ParquetColumn { name: "song_id", kind: ParquetColumnKind::Int },
+            ParquetColumn { name: "text", kind: ParquetColumnKind::Text },
+        ],
+        &Path::new(outdir).join("instruments.parquet"),
+    )?;
+
+    println!("Wrote files.parquet, samples.parquet, instruments.parquet to {}", outdir);
+
+    Ok(())
+}
+
+// Writes every file's sha256 and path as a plain "sha256<TAB>path" list, with a trailing
+// "<TAB>pattern_hash" column when the entry has a usable one (hash==0 means none) — for tools
+// that can't talk SQLite or Parquet and just want a flat checksum list to diff or dedupe against.
+fn export_hashlist(path: &str, db: &Connection) -> Result<()> {
+    let mut stmnt = db.prepare("SELECT hash_id, url, pattern_hash FROM files")?;
+    let mut rows = stmnt.query([])?;
+
+    let mut out = String::new();
+    let mut count = 0;
+
+    while let Some(row) = rows.next()? {
+        let hash_id: String = row.get(0)?;
+        let url: String = row.get(1)?;
+        let pattern_hash: Option<i64> = row.get(2)?;
+
+        out += &hash_id;
+        out += "\t";
+        out += &url;
+
+        if let Some(pattern_hash) = pattern_hash {
+            if pattern_hash != 0 {
+                out += &format!("\t{}", pattern_hash);
+            }
+        }
+
+        out += "\n";
+        count += 1;
+    }
+
+    std::fs::write(path, out)?;
+
+    println!("Wrote {} entries to {}", count, path);
+
+    Ok(())
+}
+
+fn ensure_tags_table(db: &Connection) -> Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+        song_id INTEGER NOT NULL,
+        tag TEXT NOT NULL,
+        UNIQUE(song_id, tag)
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+// Applies `tag` to every database entry matching `pattern`: an exact hash_id match if `pattern`
+// looks like a sha256 hex digest, otherwise a regex/glob against the url (see
+// Filters::compile_path_pattern) — so curators can tag either a specific known duplicate or a
+// whole swath of a mirror without a separate --by-hash/--by-pattern flag to choose between them.
+fn apply_tag(pattern: &str, tag: &str, db: &Connection) -> Result<()> {
+    ensure_tags_table(db)?;
+
+    let is_hash = pattern.len() == 64 && pattern.chars().all(|c| c.is_ascii_hexdigit());
+    let regex = if is_hash { None } else { Some(Filters::compile_path_pattern(pattern)) };
+
+    let mut stmnt = db.prepare("SELECT song_id, hash_id, url FROM files")?;
+    let mut rows = stmnt.query([])?;
+
+    let mut count = 0;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let hash_id: String = row.get(1)?;
+        let url: String = row.get(2)?;
+
+        let matches = match regex.as_ref() {
+            Some(regex) => regex.is_match(&url),
+            None => hash_id == pattern,
+        };
+
+        if matches {
+            db.execute("INSERT OR IGNORE INTO tags (song_id, tag) VALUES (?1, ?2)", params![song_id, tag])?;
+            count += 1;
+        }
+    }
+
+    println!("Tagged {} file(s) with '{}'", count, tag);
+
+    Ok(())
+}
+
+// Lists every database entry carrying `tag` (see --tag).
+fn print_tag_report(tag: &str, db: &Connection) -> Result<()> {
+    ensure_tags_table(db)?;
+
+    let mut stmnt = db.prepare(
+        "SELECT files.url FROM files JOIN tags ON files.song_id = tags.song_id \
+         WHERE tags.tag = ?1 ORDER BY files.url",
+    )?;
+    let mut rows = stmnt.query(params![tag])?;
+
+    let mut count = 0;
+
+    while let Some(row) = rows.next()? {
+        let url: String = row.get(0)?;
+        println!("{}", get_url(&url));
+        count += 1;
+    }
+
+    if count == 0 {
+        println!("No entries tagged '{}'", tag);
+    }
+
+    Ok(())
+}
+
+// Dumps the `tags` table to a "song_id<TAB>tag" sidecar file, called right before a
+// --download-database re-download overwrites the database file (see get_tags_filename).
+fn export_tags(db: &Connection, path: &str) -> Result<()> {
+    let mut stmnt = db.prepare("SELECT song_id, tag FROM tags")?;
+    let mut rows = stmnt.query([])?;
+
+    let mut out = String::new();
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let tag: String = row.get(1)?;
+        out += &format!("{}\t{}\n", song_id, tag);
+    }
+
+    std::fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Restores tags stashed by export_tags into the freshly (re-)opened database. A no-op if no
+// sidecar file exists yet, e.g. the very first run, or a database that's never been tagged.
+fn import_tags(db: &Connection, path: &str) -> Result<()> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+
+    ensure_tags_table(db)?;
+
+    let contents = std::fs::read_to_string(path)?;
+
+    for line in contents.lines() {
+        let Some((song_id, tag)) = line.split_once('\t') else { continue };
+        let Ok(song_id) = song_id.parse::<u64>() else { continue };
+
+        db.execute("INSERT OR IGNORE INTO tags (song_id, tag) VALUES (?1, ?2)", params![song_id, tag])?;
+    }
+
+    Ok(())
+}
+
+fn ensure_annotations_table(db: &Connection) -> Result<()> {
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS annotations (
+        hash_id TEXT NOT NULL UNIQUE,
+        label TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+// Imports a "sha256,label" CSV into the `annotations` table, keyed on the sample's sha256
+// (samples.hash_id) rather than song_id, since the same sample sha256 can turn up in many
+// unrelated songs. Re-importing a hash replaces its label rather than erroring, so a curator
+// fixing a provenance sheet doesn't need to clear the table first.
+fn import_annotations(path: &str, db: &Connection) -> Result<()> {
+    ensure_annotations_table(db)?;
+
+    let contents = std::fs::read_to_string(path)?;
+    let mut count = 0;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some((hash_id, label)) = line.split_once(',') else { continue };
+        let hash_id = hash_id.trim().trim_matches('"');
+        let label = label.trim().trim_matches('"');
+
+        if hash_id.is_empty() || label.is_empty() {
+            continue;
+        }
+
+        db.execute(
+            "INSERT INTO annotations (hash_id, label) VALUES (?1, ?2)
+             ON CONFLICT(hash_id) DO UPDATE SET label = excluded.label",
+            params![hash_id, label],
+        )?;
+        count += 1;
+    }
+
+    println!("Imported {} annotation(s) from {}", count, path);
+
+    Ok(())
+}
+
+// Builds a sample-reuse graph over the whole database: one node per song, one edge per pair of
+// songs sharing at least one sample hash, weighted by how many they share. Only considers
+// candidate pairs that actually share a sample (via hash_to_songs), same as print_superset_report,
+// since a bare O(n^2) walk over every song pair isn't feasible here. Songs that share no sample
+// with anything are left out entirely rather than added as isolated nodes — they'd add nothing to
+// a graph whose whole point is visualizing reuse.
+type SampleGraphNodes = HashMap<u64, String>;
+type SampleGraphEdges = HashMap<(u64, u64), u32>;
+
+fn build_sample_graph(db: &Connection) -> Result<(SampleGraphNodes, SampleGraphEdges)> {
+    let songs = get_song_sample_sets(db)?;
+
+    let mut hash_to_songs: HashMap<&String, Vec<u64>> = HashMap::new();
+    for (song_id, (_, samples)) in &songs {
+        for hash in samples {
+            hash_to_songs.entry(hash).or_default().push(*song_id);
+        }
+    }
+
+    let mut edges: HashMap<(u64, u64), u32> = HashMap::new();
+    for song_ids in hash_to_songs.values() {
+        for i in 0..song_ids.len() {
+            for j in (i + 1)..song_ids.len() {
+                let (a, b) = (song_ids[i], song_ids[j]);
+                let key = (std::cmp::min(a, b), std::cmp::max(a, b));
+                *edges.entry(key).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut referenced = HashSet::new();
+    for (a, b) in edges.keys() {
+        referenced.insert(*a);
+        referenced.insert(*b);
+    }
+
+    let nodes = songs
+        .into_iter()
+        .filter(|(song_id, _)| referenced.contains(song_id))
+        .map(|(song_id, (url, _))| (song_id, url))
+        .collect();
+
+    Ok((nodes, edges))
+}
+
+fn write_gexf_graph(nodes: &SampleGraphNodes, edges: &SampleGraphEdges, path: &str) -> Result<()> {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out += "<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n";
+    out += "  <graph mode=\"static\" defaultedgetype=\"undirected\">\n";
+    out += "    <nodes>\n";
+
+    for (song_id, url) in nodes {
+        out += &format!("      <node id=\"{}\" label=\"{}\" />\n", song_id, html_escape(url));
+    }
+
+    out += "    </nodes>\n";
+    out += "    <edges>\n";
+
+    for (i, ((a, b), weight)) in edges.iter().enumerate() {
+        out += &format!(
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\" />\n",
+            i, a, b, weight
+        );
+    }
+
+    out += "    </edges>\n";
+    out += "  </graph>\n";
+    out += "</gexf>\n";
+
+    std::fs::write(path, out)?;
+
+    Ok(())
+}
+
+fn write_dot_graph(nodes: &SampleGraphNodes, edges: &SampleGraphEdges, path: &str) -> Result<()> {
+    let mut out = String::from("graph sample_reuse {\n");
+
+    for (song_id, url) in nodes {
+        out += &format!("  {} [label=\"{}\"];\n", song_id, url.replace('"', "\\\""));
+    }
+
+    for ((a, b), weight) in edges {
+        out += &format!("  {} -- {} [weight={}];\n", a, b, weight);
+    }
+
+    out += "}\n";
+
+    std::fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Dumps the whole-database sample-reuse graph (see build_sample_graph) as GEXF or DOT, picked
+// from `path`'s file extension, for loading into Gephi or Graphviz.
+fn export_sample_graph(path: &str, db: &Connection) -> Result<()> {
+    let (nodes, edges) = build_sample_graph(db)?;
+
+    match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("gexf") => write_gexf_graph(&nodes, &edges, path)?,
+        Some("dot") => write_dot_graph(&nodes, &edges, path)?,
+        _ => bail!("--export-sample-graph expects a \".gexf\" or \".dot\" file extension, got \"{}\"", path),
+    }
+
+    println!(
+        "Wrote sample reuse graph ({} nodes, {} edges) to {}",
+        nodes.len(),
+        edges.len(),
+        path
+    );
+
+    Ok(())
+}
+
+// Get the target filename
+fn get_db_filename() -> String {
+    let db_filename = PROFILE_DB_FILENAME.get().map(String::as_str).unwrap_or(DB_FILENAME);
+    let p = std::env::current_exe().unwrap();
+    let path = Path::new(&p);
+    let path = path.parent().unwrap().join(db_filename);
+    path.into_os_string().into_string().unwrap()
+}
+
+fn get_db_remote() -> &'static str {
+    PROFILE_DB_REMOTE.get().map(String::as_str).unwrap_or(DB_REMOTE)
+}
+
+// The zstd counterpart of get_db_remote(), derived rather than configured separately: published
+// database artifacts are expected to sit side by side (same path, different extension), so a
+// second --profile/--config knob just for this would be one more thing to keep in sync for no
+// real benefit.
+fn get_db_remote_zstd() -> String {
+    get_db_remote().replacen(".db.7z", ".db.zst", 1)
+}
+
+// Picks "7z" or "zstd" for the database artifact to fetch: a forced choice via --db-format, or
+// (the default, "auto") negotiated by HEAD-checking whether a zstd artifact is actually published
+// next to the 7z one, falling back to 7z if that check fails or comes back empty-handed.
+fn resolve_db_format(args: &Args) -> &'static str {
+    match args.db_format.as_str() {
+        "zstd" => "zstd",
+        "7z" => "7z",
+        _ => match ureq::head(&get_db_remote_zstd()).call() {
+            Ok(resp) if resp.status() == 200 => "zstd",
+            _ => "7z",
+        },
+    }
+}
+
+// Side-file next to the database holding the hash_id/pattern_hash bloom filters (see
+// BloomFilters), so a --match-dir scan can skip SQLite entirely for the common "definitely
+// not in the database" case.
+fn get_bloom_filename() -> String {
+    format!("{}.bloom", get_db_filename())
+}
+
+// Side-file next to the database holding every "song_id<TAB>tag" pair added via --tag. The
+// `tags` table itself lives inside the database, but the database file gets wiped wholesale on
+// every --download-database re-download, so curator-applied tags are stashed here beforehand
+// and restored into the fresh database's `tags` table afterward (see export_tags/import_tags).
+fn get_tags_filename() -> String {
+    format!("{}.tags", get_db_filename())
+}
+
+// Side-file next to the database recording the remote's Last-Modified header as of the last
+// successful download, so --check-update can tell a newer database is available with a cheap HEAD
+// request instead of re-downloading the whole archive to compare.
+fn get_remote_meta_filename() -> String {
+    format!("{}.remote-meta", get_db_filename())
+}
+
+// A fixed-size bit array consulted before a hash_id/pattern_hash lookup hits SQLite: "maybe
+// present" means fall through to the real query, "definitely absent" skips it outright. Sized
+// for roughly a 1% false-positive rate at `num_items` entries, per the standard bloom filter
+// sizing formulas (m = -n*ln(p)/ln(2)^2, k = (m/n)*ln(2)). Bits live in AtomicU64s so the build
+// pass can insert from every worker thread without a lock.
+struct BloomFilter {
+    bits: Vec<std::sync::atomic::AtomicU64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    fn new(num_items: usize) -> BloomFilter {
+        let num_items = (num_items.max(1)) as f64;
+        let false_positive_rate = 0.01_f64;
+        let num_bits = (-(num_items * false_positive_rate.ln()) / std::f64::consts::LN_2.powi(2)).ceil();
+        let num_words = ((num_bits as u64).max(64)).div_ceil(64);
+        let num_bits = num_words * 64;
+        let num_hashes = (((num_bits as f64) / num_items) * std::f64::consts::LN_2).round().max(1.0) as u32;
+
+        BloomFilter {
+            bits: (0..num_words).map(|_| std::sync::atomic::AtomicU64::new(0)).collect(),
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    // Derives `num_hashes` independent bit positions from two 64-bit hashes of `key` via
+    // double hashing (Kirsch-Mitzenmacher), avoiding a real hash computation per slot.
+    fn bit_positions(&self, key: &[u8]) -> Vec<u64> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        let h1 = hasher.finish();
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        0x9E3779B97F4A7C15u64.hash(&mut hasher);
+        let h2 = hasher.finish();
+
+        (0..self.num_hashes)
+            .map(|i| h1.wrapping_add((i as u64).wrapping_mul(h2)) % self.num_bits)
+            .collect()
+    }
+
+    fn insert(&self, key: &[u8]) {
+        use std::sync::atomic::Ordering::Relaxed;
+        for bit in self.bit_positions(key) {
+            self.bits[(bit / 64) as usize].fetch_or(1u64 << (bit % 64), Relaxed);
+        }
+    }
+
+    fn contains(&self, key: &[u8]) -> bool {
+        use std::sync::atomic::Ordering::Relaxed;
+        self.bit_positions(key)
+            .into_iter()
+            .all(|bit| self.bits[(bit / 64) as usize].load(Relaxed) & (1u64 << (bit % 64)) != 0)
+    }
+
+    // On-disk format: num_bits (u64 LE), num_hashes (u32 LE), then the bit array as u64 LE words.
+    fn write(&self, out: &mut Vec<u8>) {
+        use std::sync::atomic::Ordering::Relaxed;
+        out.extend_from_slice(&self.num_bits.to_le_bytes());
+        out.extend_from_slice(&self.num_hashes.to_le_bytes());
+        for word in &self.bits {
+            out.extend_from_slice(&word.load(Relaxed).to_le_bytes());
+        }
+    }
+
+    fn read(data: &[u8]) -> Result<(BloomFilter, &[u8])> {
+        if data.len() < 12 {
+            bail!("bloom filter data is truncated");
+        }
+
+        let num_bits = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        let num_hashes = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        let num_words = (num_bits / 64) as usize;
+        let words_end = 12 + num_words * 8;
+
+        if data.len() < words_end {
+            bail!("bloom filter data is truncated");
+        }
+
+        let bits = data[12..words_end]
+            .chunks_exact(8)
+            .map(|chunk| std::sync::atomic::AtomicU64::new(u64::from_le_bytes(chunk.try_into().unwrap())))
+            .collect();
+
+        Ok((BloomFilter { bits, num_bits, num_hashes }, &data[words_end..]))
+    }
+}
+
+// Bundles the two bloom filters built alongside the database (see get_bloom_filename): one over
+// every hash_id (sha256), one over every non-zero pattern_hash. Loading is best-effort — older
+// databases simply don't have a side-file yet, in which case every lookup falls through to
+// SQLite as before.
+struct BloomFilters {
+    hash_id: BloomFilter,
+    pattern_hash: BloomFilter,
+}
+
+impl BloomFilters {
+    fn load(path: &str) -> Result<BloomFilters> {
+        let data = std::fs::read(path)?;
+        let (hash_id, rest) = BloomFilter::read(&data)?;
+        let (pattern_hash, _) = BloomFilter::read(rest)?;
+        Ok(BloomFilters { hash_id, pattern_hash })
+    }
+
+    fn load_optional(path: &str) -> Option<BloomFilters> {
+        BloomFilters::load(path).ok()
+    }
+
+    fn save(&self, path: &str) -> Result<()> {
+        let mut out = Vec::new();
+        self.hash_id.write(&mut out);
+        self.pattern_hash.write(&mut out);
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+}
+
+enum DbCommand {
+    Insert(String), // Example command to insert a string
+    Quit,           // Example command to query a string
+}
+
+fn run_build_db_thread(filename: String, rx: Receiver<DbCommand>) -> Result<()> {
+    let conn = Connection::open(filename).expect("Failed to open database");
+
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+
+    conn.execute(
+        "CREATE TABLE files (
+        song_id INTEGER PRIMARY KEY,
+        hash_id TEXT NOT NULL,
+        pattern_hash INTEGER,
+        normalized_pattern_hash INTEGER,
+        canonical_hash INTEGER,
+        is_empty_pattern INTEGER,
+        render_hash INTEGER,
+        pair_hash TEXT,
+        companion_url TEXT,
+        parse_status TEXT,
+        parse_error TEXT,
+        warnings TEXT,
+        format TEXT,
+        backend TEXT,
+        channel_count INTEGER,
+        sample_count INTEGER,
+        instrument_count INTEGER,
+        file_size INTEGER,
+        mtime_unix INTEGER,
+        pattern_text TEXT,
+        url TEXT NOT NULL
+        )",
+        [],
+    )
+    .unwrap();
+
+    conn.execute(
+        "CREATE TABLE samples (
+        hash_id TEXT,
+        song_id INTEGER,
+        song_sample_id INTEGER,
+        text TEXT NOT NULL,
+        length_bytes INTEGER,
+        length INTEGER,
+        c5_speed INTEGER,
+        pan INTEGER,
+        volume INTEGER,
+        global_vol INTEGER,
+        stereo INTEGER,
+        sample_bits INTEGER,
+        relative_tone INTEGER,
+        fine_tune INTEGER,
+        vibrato_type INTEGER,
+        vibrato_sweep INTEGER,
+        vibrato_depth INTEGER,
+        vibrato_rate INTEGER,
+        fingerprint_head_hash TEXT,
+        fingerprint_tail_hash TEXT,
+        fingerprint_rms REAL,
+        FOREIGN KEY (song_id) REFERENCES files(song_id)
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE instruments (
+        hash_id TEXT,
+        song_id INTEGER,
+        text TEXT,
+        FOREIGN KEY (song_id) REFERENCES files(song_id)
+        )",
+        [],
+    )?;
+
+    // FTS5 virtual table for the song message text (IT/XM/S3M embedded "comment"); song_id is
+    // stored unindexed since it's only ever used to join back to files, never searched itself.
+    // Virtual tables can't carry a FOREIGN KEY constraint, so the link to files is by convention
+    // only, same as hash_id on samples/instruments above.
+    conn.execute(
+        "CREATE VIRTUAL TABLE messages USING fts5(
+        text,
+        song_id UNINDEXED
+        )",
+        [],
+    )?;
+
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    // Listen for commands
+    for command in rx {
+        match command {
+            DbCommand::Insert(cmd) => {
+                conn.execute(&cmd, [])?;
+            }
+            DbCommand::Quit => break,
+        }
+    }
+
+    conn.execute("COMMIT", [])?;
+    conn.execute("CREATE INDEX hash_files ON files (hash_id)", [])?;
+    conn.execute("CREATE INDEX pattern_files ON files (pattern_hash)", [])?;
+    conn.execute("CREATE INDEX canonical_files ON files (canonical_hash)", [])?;
+    conn.execute("CREATE INDEX render_files ON files (render_hash)", [])?;
+    conn.execute("CREATE INDEX pair_files ON files (pair_hash)", [])?;
+    conn.execute("CREATE INDEX parse_status_files ON files (parse_status)", [])?;
+    conn.execute("CREATE INDEX hash_samples ON samples (hash_id)", [])?;
+    conn.execute("CREATE INDEX length_samples ON samples (length)", [])?;
+    conn.execute("CREATE INDEX song_id_samples ON samples (song_id)", [])?;
+
+    Ok(())
+}
+
+// Rough single-threaded scan rate assumption for the --dry-run time estimate below, informally
+// observed across MOD/XM/S3M-heavy corpora with libopenmpt and sha256. A guess, not a
+// measurement: --dry-run exists specifically to avoid parsing anything, so there's no better
+// number available without doing the real work it lets you skip.
+const DRY_RUN_MB_PER_SEC_PER_THREAD: f64 = 8.0;
+
+// Walks database_path the same way --build-database would, but only tallies file counts, total
+// size, and a per-extension breakdown - no file is ever opened past a metadata() call. Meant to
+// let a maintainer sanity-check a source path and its filters before kicking off a run that might
+// take hours.
+fn print_build_database_dry_run(database_path: &str, args: &Args) {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        database_path,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    let format_stats = FormatStats::new();
+    let mut total_size: u64 = 0;
+
+    for file in &files {
+        let size = std::fs::metadata(long_path(Path::new(file))).map(|m| m.len()).unwrap_or(0);
+        total_size += size;
+        format_stats.record(&get_format_extension(file), size);
+    }
+
+    let total_mb = total_size as f64 / (1024.0 * 1024.0);
+    let threads = rayon::current_num_threads().max(1);
+    let estimated_secs = total_mb / (DRY_RUN_MB_PER_SEC_PER_THREAD * threads as f64);
+
+    println!("Dry run for \"{}\":", database_path);
+    println!("  files:      {}", files.len());
+    println!("  total size: {} bytes ({:.2} MB)", total_size, total_mb);
+    format_stats.print_table();
+    println!(
+        "  estimated build time: {:.1}s (rough estimate assuming ~{:.0} MB/s/thread across {} thread(s); no files were parsed)",
+        estimated_secs, DRY_RUN_MB_PER_SEC_PER_THREAD, threads
+    );
+
+    errors.finish();
+}
+
+fn build_database(out_filename: &str, database_path: &str, args: &Args) {
+    // Channel for sending commands to the database thread
+    let (tx, rx): (Sender<DbCommand>, Receiver<DbCommand>) = mpsc::channel();
+
+    let filename = out_filename.to_owned();
+
+    // Spawn the database thread
+    let db_thread = std::thread::spawn(move || {
+        run_build_db_thread(filename, rx).unwrap();
+    });
+
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        database_path,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    let spinner_style =
+        ProgressStyle::with_template("{prefix:.bold.dim} {wide_bar} {pos}/{len}").unwrap();
+
+    let progress_json = args.progress == "json";
+
+    let pb = ProgressBar::new(files.len() as _);
+    if progress_json {
+        pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    } else {
+        pb.set_style(spinner_style);
+        pb.set_prefix("Building database");
+    }
+
+    let total_files = files.len();
+    let hashed_count = std::sync::atomic::AtomicUsize::new(0);
+    let ok_count = std::sync::atomic::AtomicUsize::new(0);
+    let unsupported_count = std::sync::atomic::AtomicUsize::new(0);
+    let corrupt_count = std::sync::atomic::AtomicUsize::new(0);
+    let crashed_count = std::sync::atomic::AtomicUsize::new(0);
+    let timed_out_count = std::sync::atomic::AtomicUsize::new(0);
+    let unreadable_count = std::sync::atomic::AtomicUsize::new(0);
+
+    let worker_exe = if args.worker_pool {
+        Some(std::env::current_exe().expect("Failed to resolve current executable path"))
+    } else {
+        None
+    };
+
+    let bloom_filters = BloomFilters {
+        hash_id: BloomFilter::new(files.len()),
+        pattern_hash: BloomFilter::new(files.len()),
+    };
+
+    let shutdown_skipped = std::sync::atomic::AtomicUsize::new(0);
+    let format_stats = FormatStats::new();
+
+    files.par_iter().enumerate().for_each(|(index, input_path)| {
+        // Already-started files are allowed to finish so the DB thread only ever sees whole
+        // records, but once a shutdown signal lands, don't start parsing any file we haven't
+        // reached yet.
+        if is_shutdown_requested() {
+            shutdown_skipped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            return;
+        }
+
+        let mut track = match &worker_exe {
+            Some(exe) => get_track_info_isolated(
+                exe,
+                input_path,
+                args.dump_patterns,
+                args.samples_only,
+                args.no_sample_hashes,
+                args.render_fingerprint_seconds,
+                &args.sample_charset,
+                args.worker_timeout_seconds,
+                args.worker_memory_limit_mb,
+            ),
+            None => get_track_info(
+                input_path,
+                args.dump_patterns,
+                args.samples_only,
+                args.no_sample_hashes,
+                args.render_fingerprint_seconds,
+                &args.sample_charset,
+                &errors,
+                args.fail_fast,
+            ),
+        };
+        track.filename = normalize_stored_path(input_path, database_path, args);
+
+        // Best-effort: a file that vanished or whose metadata can't be read between the scan and
+        // here just gets a NULL mtime rather than aborting the whole build.
+        let mtime_unix = std::fs::metadata(input_path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_else(|| "NULL".to_string());
+
+        let t = track.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+        let pattern_hash = if t != 0 {
+            format!("{}", t)
+        } else {
+            "NULL".to_string()
+        };
+
+        let t = track.normalized_pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+        let normalized_pattern_hash = if t != 0 {
+            format!("{}", t)
+        } else {
+            "NULL".to_string()
+        };
+
+        let t = track.canonical_hash & 0x7FFF_FFFF_FFFF_FFFF;
+        let canonical_hash = if t != 0 {
+            format!("{}", t)
+        } else {
+            "NULL".to_string()
+        };
+
+        let is_empty_pattern = track.is_empty_pattern as u32;
+
+        let render_hash = match track.render_hash {
+            Some(hash) => format!("{}", hash),
+            None => "NULL".to_string(),
+        };
+
+        let pair_hash = match track.pair_hash.as_ref() {
+            Some(hash) => format!("'{}'", hash),
+            None => "NULL".to_string(),
+        };
+
+        let companion_url = match track.companion_url.as_ref() {
+            Some(url) => format!("'{}'", get_stored_url(url)),
+            None => "NULL".to_string(),
+        };
+
+        let parse_error = match track.parse_error.as_ref() {
+            Some(err) => format!("'{}'", err.replace('\'', "''")),
+            None => "NULL".to_string(),
+        };
+
+        let warnings = match track.warnings.as_ref() {
+            Some(w) => format!("'{}'", w.replace('\'', "''")),
+            None => "NULL".to_string(),
+        };
+
+        if let (Some(dir), Some(text)) = (args.dump_patterns_dir.as_ref(), track.pattern_text.as_ref()) {
+            if let Err(err) = write_pattern_dump(dir, &track.sha256_hash, text) {
+                log::warn!("Failed to write pattern dump for \"{}\": {}", track.filename, err);
+            }
+        }
+
+        let pattern_text = match (args.store_pattern_text, track.pattern_text.as_ref()) {
+            (true, Some(text)) => format!("'{}'", text.replace('\'', "''")),
+            _ => "NULL".to_string(),
+        };
+
+        bloom_filters.hash_id.insert(track.sha256_hash.as_bytes());
+        let masked_pattern_hash = track.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+        if masked_pattern_hash != 0 {
+            bloom_filters.pattern_hash.insert(&masked_pattern_hash.to_le_bytes());
+        }
+
+        let insert = format!("INSERT INTO files (song_id, hash_id, pattern_hash, normalized_pattern_hash, canonical_hash, is_empty_pattern, render_hash, pair_hash, companion_url, parse_status, parse_error, warnings, format, backend, channel_count, sample_count, instrument_count, file_size, mtime_unix, pattern_text, url) VALUES ({}, '{}', {}, {}, {}, {}, {}, {}, {}, '{}', {}, {}, '{}', '{}', {}, {}, {}, {}, {}, {}, '{}')",
+                index,
+                &track.sha256_hash,
+                pattern_hash,
+                normalized_pattern_hash,
+                canonical_hash,
+                is_empty_pattern,
+                render_hash,
+                pair_hash,
+                companion_url,
+                &track.parse_status,
+                parse_error,
+                warnings,
+                &track.format,
+                &track.backend,
+                track.channel_count,
+                track.sample_count,
+                track.instrument_count,
+                track.file_size,
+                mtime_unix,
+                pattern_text,
+                get_stored_url(&track.filename));
+
+        tx.send(DbCommand::Insert(insert)).expect("Failed to send command");
+
+        match track.parse_status.as_str() {
+            "ok" => ok_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            "corrupt" => corrupt_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            "crashed" => crashed_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            "timed_out" => timed_out_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            "unreadable" => unreadable_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            _ => unsupported_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+        };
+
+        let format_key = if track.parse_status == "ok" {
+            track.format.to_ascii_uppercase()
+        } else {
+            track.parse_status.clone()
+        };
+        format_stats.record(&format_key, track.file_size);
+
+        if let Some(parse_error) = track.parse_error.as_ref() {
+            errors.record(&track.filename, format!("{} ({})", parse_error, track.parse_status));
+        }
+
+        for sample in &track.samples {
+            let (fingerprint_head_hash, fingerprint_tail_hash, fingerprint_rms) =
+                match (args.store_sample_fingerprints, sample.fingerprint.as_ref()) {
+                    (true, Some(f)) => {
+                        (format!("'{}'", f.head_hash), format!("'{}'", f.tail_hash), f.rms.to_string())
+                    }
+                    _ => ("NULL".to_string(), "NULL".to_string(), "NULL".to_string()),
+                };
+
+            let insert = format!("INSERT INTO samples (hash_id, song_id, song_sample_id, text, length_bytes, length, c5_speed, pan, volume, global_vol, stereo, sample_bits, relative_tone, fine_tune, vibrato_type, vibrato_sweep, vibrato_depth, vibrato_rate, fingerprint_head_hash, fingerprint_tail_hash, fingerprint_rms) VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {})",
+                &sample.sha256_hash,
+                index,
+                sample.sample_id,
+                &sample.text,
+                sample.length_bytes,
+                sample.length,
+                sample.c5_speed,
+                sample.pan,
+                sample.volume,
+                sample.global_vol,
+                sample.stereo,
+                sample.bits_per_sample,
+                sample.relative_tone,
+                sample.fine_tune,
+                sample.vib_type,
+                sample.vib_sweep,
+                sample.vib_depth,
+                sample.vib_rate,
+                fingerprint_head_hash,
+                fingerprint_tail_hash,
+                fingerprint_rms);
+
+            tx.send(DbCommand::Insert(insert)).expect("Failed to send command");
+        }
+
+        for instrument in &track.instrument_names {
+            let insert = format!(
+                "INSERT INTO instruments (hash_id, song_id, text) VALUES (NULL, {}, {})",
+                index, instrument
+            );
+
+            tx.send(DbCommand::Insert(insert)).expect("Failed to send command");
+        }
+
+        if let Some(message) = track.message.as_ref() {
+            let insert = format!(
+                "INSERT INTO messages (text, song_id) VALUES ('{}', {})",
+                message.replace('\'', "''"),
+                index
+            );
+
+            tx.send(DbCommand::Insert(insert)).expect("Failed to send command");
+        }
+
+        if progress_json {
+            let processed = hashed_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            emit_json_progress("hashing", input_path, processed, total_files);
+        } else {
+            pb.inc(1);
+        }
+    });
+
+    println!(
+        "Parsed ok: {}, unsupported: {}, corrupt: {}, crashed: {}, timed out: {}, unreadable: {}",
+        ok_count.load(std::sync::atomic::Ordering::Relaxed),
+        unsupported_count.load(std::sync::atomic::Ordering::Relaxed),
+        corrupt_count.load(std::sync::atomic::Ordering::Relaxed),
+        crashed_count.load(std::sync::atomic::Ordering::Relaxed),
+        timed_out_count.load(std::sync::atomic::Ordering::Relaxed),
+        unreadable_count.load(std::sync::atomic::Ordering::Relaxed),
+    );
+
+    format_stats.print_table();
+
+    let shutdown_skipped = shutdown_skipped.load(std::sync::atomic::Ordering::Relaxed);
+    if shutdown_skipped > 0 {
+        println!(
+            "Shutdown requested: stopped after {} of {} files. The database below only covers the files that were processed.",
+            total_files - shutdown_skipped, total_files,
+        );
+    }
+
+    errors.finish();
+
+    println!("Writing database...");
+
+    tx.send(DbCommand::Quit).expect("Failed to send command");
+    db_thread.join().unwrap();
+
+    if let Err(err) = bloom_filters.save(&get_bloom_filename()) {
+        log::warn!("Failed to write bloom filter side-file: {}", err);
+    }
+
+    println!("Done");
+}
+
+fn create_db_file(filename: &str) -> Result<File> {
+    if let Ok(file) = File::create(filename) {
+        return Ok(file);
+    }
+
+    bail!(
+        "Tried to create database at {} but was unable to do so. Manually download {} and place it next to the modland_has executable",
+        filename, get_db_remote(),
+    )
+}
+
+// Emits one newline-delimited JSON progress event to stderr for --progress json. `total` of 0
+// means the total isn't known yet (e.g. still walking the directory tree).
+fn emit_json_progress(phase: &str, current_file: &str, processed: usize, total: usize) {
+    eprintln!(
+        "{{\"phase\": \"{}\", \"current_file\": \"{}\", \"processed\": {}, \"total\": {}}}",
+        phase,
+        current_file.replace('\\', "\\\\").replace('"', "\\\""),
+        processed,
+        total,
+    );
+}
+
+fn create_progress_bar(len: usize) -> ProgressBar {
+    let pb = ProgressBar::new(len as _);
+    //pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{prefix} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})",
+        )
+        .unwrap()
+        .with_key(
+            "eta",
+            |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+            },
+        )
+        .progress_chars("#>-"),
+    );
+    pb
+}
+
+// Download and upack the database
+fn download_db() -> Result<ProgressBar> {
+    let filename = format!("{}.7z", get_db_filename());
+    // Written to a temp name and renamed into place only once the whole download has landed, so an
+    // interrupted or crashed download never leaves a half-written file at the name decompress_db()
+    // looks for.
+    let tmp_filename = format!("{}.part", filename);
+    let mut file = create_db_file(&tmp_filename)?;
+
+    let resp = ureq::get(get_db_remote()).call()?;
+    let len: usize = resp.header("Content-Length").unwrap().parse()?;
+    let last_modified = resp.header("Last-Modified").map(|s| s.to_string());
+
+    let mut temp_buffer: [u8; 1024] = [0; 1024];
+    let mut reader = resp.into_reader();
+
+    let pb = create_progress_bar(len);
+
+    pb.set_prefix("Downloading Database");
+
+    let mut pos = 0;
+
+    loop {
+        if is_shutdown_requested() {
+            drop(file);
+            let _ = std::fs::remove_file(&tmp_filename);
+            bail!(
+                "Shutdown requested: download interrupted at {} of {} bytes, removed partial {}",
+                pos, len, tmp_filename,
+            );
+        }
+
+        let read_size = reader.read(&mut temp_buffer)?;
+
+        if read_size == 0 {
+            break;
+        }
+
+        pb.set_position(pos);
+        pos += read_size as u64;
+
+        file.write_all(&temp_buffer[0..read_size])?;
+    }
+
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp_filename, &filename)?;
+
+    if let Some(last_modified) = last_modified {
+        if let Err(err) = std::fs::write(get_remote_meta_filename(), &last_modified) {
+            log::warn!("Failed to write remote metadata side-file: {}", err);
+        }
+    }
+
+    Ok(pb)
+}
+
+// The zstd counterpart of download_db(): same temp-name-then-rename dance, fetched from
+// get_db_remote_zstd() into a ".zst" file instead of ".7z".
+fn download_db_zstd() -> Result<ProgressBar> {
+    let filename = format!("{}.zst", get_db_filename());
+    let tmp_filename = format!("{}.part", filename);
+    let mut file = create_db_file(&tmp_filename)?;
+
+    let resp = ureq::get(&get_db_remote_zstd()).call()?;
+    let len: usize = resp.header("Content-Length").unwrap().parse()?;
+    let last_modified = resp.header("Last-Modified").map(|s| s.to_string());
+
+    let mut temp_buffer: [u8; 1024] = [0; 1024];
+    let mut reader = resp.into_reader();
+
+    let pb = create_progress_bar(len);
+
+    pb.set_prefix("Downloading Database (zstd)");
+
+    let mut pos = 0;
+
+    loop {
+        if is_shutdown_requested() {
+            drop(file);
+            let _ = std::fs::remove_file(&tmp_filename);
+            bail!(
+                "Shutdown requested: download interrupted at {} of {} bytes, removed partial {}",
+                pos, len, tmp_filename,
+            );
+        }
+
+        let read_size = reader.read(&mut temp_buffer)?;
+
+        if read_size == 0 {
+            break;
+        }
+
+        pb.set_position(pos);
+        pos += read_size as u64;
+
+        file.write_all(&temp_buffer[0..read_size])?;
+    }
+
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&tmp_filename, &filename)?;
+
+    if let Some(last_modified) = last_modified {
+        if let Err(err) = std::fs::write(get_remote_meta_filename(), &last_modified) {
+            log::warn!("Failed to write remote metadata side-file: {}", err);
+        }
+    }
+
+    Ok(pb)
+}
+
+// Checks for a newer remote database via a HEAD request's Last-Modified header, compared against
+// the value recorded next to the local DB by the last successful download_db() (see
+// get_remote_meta_filename()). Never downloads the archive itself, so this is cheap enough for a
+// cron job to poll.
+fn check_update() -> Result<()> {
+    let resp = ureq::head(get_db_remote()).call()?;
+    let remote_last_modified = resp.header("Last-Modified").map(|s| s.to_string());
+
+    let local_last_modified = std::fs::read_to_string(get_remote_meta_filename()).ok();
+
+    match (remote_last_modified, local_last_modified) {
+        (Some(remote), Some(local)) if remote == local => {
+            println!("Database is up to date (Last-Modified: {})", remote);
+            std::process::exit(0);
+        }
+        (Some(remote), Some(local)) => {
+            println!("A newer database is available (remote: {}, local: {})", remote, local);
+            std::process::exit(1);
+        }
+        (Some(remote), None) => {
+            println!(
+                "No record of when the local database was last downloaded ({} not found); can't tell if the remote (Last-Modified: {}) is newer. Run --download-database once to record a baseline.",
+                get_remote_meta_filename(), remote,
+            );
+            std::process::exit(2);
+        }
+        (None, _) => {
+            println!("Remote did not report a Last-Modified header; can't determine freshness.");
+            std::process::exit(2);
+        }
+    }
+}
+
+// Sanity-checks a freshly decompressed database before it's allowed to replace the one the rest
+// of the program trusts: a truncated or corrupt decompression would otherwise look like a valid
+// DB file right up until the first query against it fails.
+fn verify_db_file(filename: &str) -> Result<()> {
+    let conn = Connection::open(filename)?;
+    let result: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+
+    if result != "ok" {
+        bail!("Downloaded database failed integrity check: {}", result);
+    }
+
+    conn.query_row("SELECT count(*) FROM files", [], |row| row.get::<_, i64>(0))?;
+
+    Ok(())
+}
+
+// --package-database: compresses the local database to `out_filename` and writes
+// "<out_filename>.manifest.json" next to it, covering what a maintainer currently has to gather by
+// hand before uploading a new database (row counts, hash-algorithm version, archive checksum).
+// Picks 7z or zstd by `out_filename`'s extension (".zst" vs. anything else), the same way
+// get_format_extension() picks a tracker format from a scanned file's name, so publishing both
+// artifacts for a release is just two calls with two output names.
+fn package_database(out_filename: &str) -> Result<()> {
+    let db_filename = get_db_filename();
+
+    if !Path::new(&db_filename).exists() {
+        bail!("No local database found at {} to package", db_filename);
+    }
+
+    let conn = Connection::open(&db_filename)?;
+    let file_count: i64 = conn.query_row("SELECT count(*) FROM files", [], |row| row.get(0))?;
+    let sample_count: i64 = conn.query_row("SELECT count(*) FROM samples", [], |row| row.get(0))?;
+    let instrument_count: i64 =
+        conn.query_row("SELECT count(*) FROM instruments", [], |row| row.get(0))?;
+    drop(conn);
+
+    if Path::new(out_filename).exists() {
+        std::fs::remove_file(out_filename)?;
+    }
+
+    let compression = if Path::new(out_filename).extension().is_some_and(|ext| ext == "zst") {
+        let input = File::open(&db_filename)?;
+        let output = File::create(out_filename)?;
+        zstd::stream::copy_encode(input, output, 0)?;
+        "zstd"
+    } else {
+        sevenz_rust::compress_to_path(&db_filename, out_filename)?;
+        "7z"
+    };
+
+    let archive_data = std::fs::read(out_filename)?;
+    let archive_sha256 = format!("{:x}", sha2::Sha256::digest(&archive_data));
+    let archive_size = archive_data.len();
+
+    let build_date_unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+
+    let pattern_hash_version = unsafe { get_pattern_hash_version() };
+
+    let manifest_filename = format!("{}.manifest.json", out_filename);
+    let manifest = format!(
+        "{{\n  \"build_date_unix\": {},\n  \"file_count\": {},\n  \"sample_count\": {},\n  \"instrument_count\": {},\n  \"pattern_hash_version\": {},\n  \"compression\": \"{}\",\n  \"archive_filename\": \"{}\",\n  \"archive_size\": {},\n  \"archive_sha256\": \"{}\"\n}}\n",
+        build_date_unix,
+        file_count,
+        sample_count,
+        instrument_count,
+        pattern_hash_version,
+        compression,
+        Path::new(out_filename).file_name().unwrap().to_string_lossy(),
+        archive_size,
+        archive_sha256,
+    );
+    std::fs::write(&manifest_filename, manifest)?;
+
+    println!(
+        "Packaged {} ({} files, {} samples, {} instruments) into {} ({} bytes, {}), manifest written to {}",
+        db_filename, file_count, sample_count, instrument_count, out_filename, archive_size, compression, manifest_filename,
+    );
+
+    Ok(())
+}
+
+// Read buffer for unpacking a 7z entry. The previous 1KB buffer meant a syscall (and a progress
+// bar update) for every kilobyte of a multi-hundred-MB database; 256KB is a sensible middle
+// ground between syscall overhead and not overshooting typical I/O page caches.
+const DECOMPRESS_BUFFER_SIZE: usize = 256 * 1024;
+
+// The unpack/verify/atomic-rename core shared by decompress_db() (reading a .7z that's already
+// on disk) and stream_download_and_decompress_db() (reading straight out of an in-memory download
+// buffer, so the compressed archive never touches disk at all).
+fn decompress_sevenz_entries<R: Read + Seek>(
+    mut sz: sevenz_rust::SevenZReader<R>,
+    pb: Option<ProgressBar>,
+) -> Result<()> {
+    let total_size: u64 = sz
+        .archive()
+        .files
+        .iter()
+        .filter(|e| e.has_stream())
+        .map(|e| e.size())
+        .sum();
+
+    let pb = if let Some(pb) = pb {
+        pb.set_length(total_size as _);
+        pb
+    } else {
+        create_progress_bar(total_size as _)
+    };
+
+    pb.set_prefix("Decompressing Database");
+
+    // Decompressed into a temp name, not straight onto get_db_filename(): the existing DB (if any)
+    // stays untouched and usable until the new one passes verify_db_file() below, so a corrupt or
+    // interrupted decompression can never leave the program without a working database.
+    let tmp_db_filename = format!("{}.part", get_db_filename());
+    // Opened once up front, not inside the per-entry callback: the archive is expected to hold a
+    // single file, but re-creating (and truncating) the output on every entry would silently
+    // drop everything but the last one if that ever stopped being true.
+    let mut file = File::create(&tmp_db_filename)?;
+
+    let mut uncompressed_size = 0;
+    let mut buf = vec![0u8; DECOMPRESS_BUFFER_SIZE];
+    let decompress_result = sz.for_each_entries(|_entry, reader| loop {
+        if is_shutdown_requested() {
+            return Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "shutdown requested").into());
+        }
+
+        let read_size = reader.read(&mut buf).unwrap();
+        if read_size == 0 {
+            break Ok(true);
+        }
+        file.write_all(&buf[..read_size])?;
+        uncompressed_size += read_size;
+
+        pb.set_position(uncompressed_size as _);
+    });
+
+    drop(file);
+
+    if decompress_result.is_err() && is_shutdown_requested() {
+        let _ = std::fs::remove_file(&tmp_db_filename);
+        bail!(
+            "Shutdown requested: decompression interrupted, removed partial {}.",
+            tmp_db_filename,
+        );
+    }
+
+    decompress_result.unwrap();
+
+    if let Err(err) = verify_db_file(&tmp_db_filename) {
+        let _ = std::fs::remove_file(&tmp_db_filename);
+        bail!("New database failed verification, kept the previous one in place: {}", err);
+    }
+
+    std::fs::rename(&tmp_db_filename, get_db_filename())?;
+
+    Ok(())
+}
+
+fn decompress_db(pb: Option<ProgressBar>) -> Result<()> {
+    let filename = format!("{}.7z", get_db_filename());
+
+    // Check if compressed file exists and unpack it
+    if !Path::new(&filename).exists() {
+        return Ok(());
+    }
+
+    let sz = sevenz_rust::SevenZReader::open(&filename, "pass".into()).unwrap();
+    decompress_sevenz_entries(sz, pb)?;
+
+    // delete the compressed file
+    std::fs::remove_file(&filename)?;
+
+    Ok(())
+}
+
+// The zstd counterpart of decompress_db(): a single compressed stream rather than a 7z archive of
+// entries, so there's no for_each_entries() dance, but the same temp-name/verify/atomic-rename
+// safety net applies. zstd frames don't expose the decompressed size up front without a separate
+// pass over the header, so the progress bar tracks the (known) compressed size instead of the
+// (unknown until done) uncompressed one.
+fn decompress_db_zstd(pb: Option<ProgressBar>) -> Result<()> {
+    let filename = format!("{}.zst", get_db_filename());
+
+    if !Path::new(&filename).exists() {
+        return Ok(());
+    }
+
+    let compressed_len = std::fs::metadata(&filename)?.len();
+
+    let pb = if let Some(pb) = pb {
+        pb.set_length(compressed_len);
+        pb
+    } else {
+        create_progress_bar(compressed_len as _)
+    };
+
+    pb.set_prefix("Decompressing Database (zstd)");
+
+    let tmp_db_filename = format!("{}.part", get_db_filename());
+    let mut decoder = zstd::Decoder::new(File::open(&filename)?)?;
+    let mut output = File::create(&tmp_db_filename)?;
+
+    let mut buf = vec![0u8; DECOMPRESS_BUFFER_SIZE];
+
+    loop {
+        if is_shutdown_requested() {
+            drop(output);
+            let _ = std::fs::remove_file(&tmp_db_filename);
+            bail!("Shutdown requested: decompression interrupted, removed partial {}.", tmp_db_filename);
+        }
+
+        let read_size = decoder.read(&mut buf)?;
+        if read_size == 0 {
+            break;
+        }
+
+        output.write_all(&buf[..read_size])?;
+        let mut compressed_file: &File = decoder.get_ref().get_ref();
+        pb.set_position(compressed_file.stream_position()?.min(compressed_len));
+    }
+
+    drop(output);
+
+    if let Err(err) = verify_db_file(&tmp_db_filename) {
+        let _ = std::fs::remove_file(&tmp_db_filename);
+        bail!("New database failed verification, kept the previous one in place: {}", err);
+    }
+
+    std::fs::rename(&tmp_db_filename, get_db_filename())?;
+    std::fs::remove_file(&filename)?;
+
+    Ok(())
+}
+
+// --stream-download: downloads the .7z straight into memory and decompresses it from there,
+// instead of download_db()+decompress_db()'s usual round trip through a <db>.7z file on disk.
+// 7z's header sits at the end of the archive, so it can't be parsed until the whole thing has
+// arrived - true pipe-as-it-downloads streaming isn't possible for this format, but buffering the
+// download in RAM still means the compressed bytes never get written to disk.
+fn stream_download_and_decompress_db() -> Result<()> {
+    let resp = ureq::get(get_db_remote()).call()?;
+    let len: usize = resp.header("Content-Length").unwrap().parse()?;
+    let last_modified = resp.header("Last-Modified").map(|s| s.to_string());
+
+    let pb = create_progress_bar(len);
+    pb.set_prefix("Downloading Database");
+
+    let mut archive_data = Vec::with_capacity(len);
+    let mut reader = resp.into_reader();
+    let mut chunk = [0u8; DECOMPRESS_BUFFER_SIZE];
+
+    loop {
+        if is_shutdown_requested() {
+            bail!("Shutdown requested: in-memory download interrupted, nothing was written to disk");
+        }
+
+        let read_size = reader.read(&mut chunk)?;
+        if read_size == 0 {
+            break;
+        }
+
+        archive_data.extend_from_slice(&chunk[..read_size]);
+        pb.set_position(archive_data.len() as _);
+    }
+
+    if let Some(last_modified) = last_modified {
+        if let Err(err) = std::fs::write(get_remote_meta_filename(), &last_modified) {
+            log::warn!("Failed to write remote metadata side-file: {}", err);
+        }
+    }
+
+    let cursor = std::io::Cursor::new(archive_data);
+    let sz = sevenz_rust::SevenZReader::new(cursor, len as u64, "pass".into())?;
+    decompress_sevenz_entries(sz, Some(pb))
+}
+
+// Unlinking a file doesn't fail its already-open file descriptors on Unix - the inode just stops
+// having a name, and reads against the fd keep working until it's closed. That's exploited by
+// open_compressed_db_readonly() to give --db-compressed its "nothing decompressed is ever visible
+// on disk" property. Windows locks open files against deletion, so there's nothing safe to do
+// there beyond leaving the scratch file for the OS temp directory's own cleanup.
+#[cfg(not(windows))]
+fn remove_scratch_db(path: &str) {
+    let _ = std::fs::remove_file(path);
+}
+
+#[cfg(windows)]
+fn remove_scratch_db(_path: &str) {}
+
+// --db-compressed: decompresses whichever compressed database artifact is already on disk into a
+// throwaway file under the OS temp directory, opens it read-only, and (see remove_scratch_db())
+// unlinks it immediately so the decompressed bytes never stay around between runs. Bails instead
+// of falling back to a normal download+decompress, since silently doing that would defeat the
+// point of the flag - the caller asked specifically to never keep a decompressed copy.
+fn open_compressed_db_readonly(args: &Args) -> Result<Connection> {
+    let sevenz_filename = format!("{}.7z", get_db_filename());
+    let zstd_filename = format!("{}.zst", get_db_filename());
+
+    let scratch_filename = std::env::temp_dir()
+        .join(format!("modland_hash.readonly.{}", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+
+    if Path::new(&zstd_filename).exists() && resolve_db_format(args) == "zstd" {
+        let mut decoder = zstd::Decoder::new(File::open(&zstd_filename)?)?;
+        let mut output = File::create(&scratch_filename)?;
+        std::io::copy(&mut decoder, &mut output)?;
+    } else if Path::new(&sevenz_filename).exists() {
+        let sz = sevenz_rust::SevenZReader::open(&sevenz_filename, "pass".into()).unwrap();
+        decompress_sevenz_entry_to(sz, &scratch_filename)?;
+    } else {
+        bail!(
+            "--db-compressed needs a compressed database artifact on disk ({} or {}), but neither \
+             was found; run once without --db-compressed --download-database first",
+            sevenz_filename,
+            zstd_filename,
+        );
+    }
+
+    let conn = Connection::open_with_flags(&scratch_filename, OpenFlags::SQLITE_OPEN_READ_ONLY);
+    remove_scratch_db(&scratch_filename);
+    conn.map_err(Into::into)
+}
+
+// Bare-bones variant of decompress_sevenz_entries(): writes straight to an exact destination path
+// with no progress bar, no verify_db_file() pass and no temp-name/rename dance, since the output
+// here is a throwaway scratch copy rather than the database modland_hash will keep using.
+fn decompress_sevenz_entry_to<R: Read + Seek>(mut sz: sevenz_rust::SevenZReader<R>, dest: &str) -> Result<()> {
+    let mut file = File::create(dest)?;
+    let mut buf = vec![0u8; DECOMPRESS_BUFFER_SIZE];
+
+    sz.for_each_entries(|_entry, reader| loop {
+        let read_size = reader.read(&mut buf).unwrap();
+        if read_size == 0 {
+            break Ok(true);
+        }
+        file.write_all(&buf[..read_size])?;
+    })
+    .unwrap();
+
+    Ok(())
+}
+
+/*
+    let re = Regex::new(search_string).unwrap();
+    let mut count = 0;
+
+    tracks.iter().for_each(|track| {
+        if let Some(metadata) = track.metadata.as_ref() {
+            if re.is_match(&metadata.sample_names.to_ascii_lowercase()) {
+                println!("===============================================================");
+                println!("Matching {}", track.filename);
+                println!("{}", metadata.sample_names);
+                count += 1;
+            }
+        }
+    });
+
+    println!("Total matches {}", count);
+}
+     */
+
+fn get_samples_from_song_id(db: &Connection, song_id: u64) -> Result<Vec<String>> {
+    let mut samples = Vec::new();
+
+    let mut stmnt = db.prepare("SELECT text FROM samples WHERE song_id = :song_id")?;
+    let mut rows = stmnt.query(&[(":song_id", &song_id)])?;
+
+    while let Some(row) = rows.next()? {
+        let text: String = row.get(0)?;
+        samples.push(text);
+    }
+
+    Ok(samples)
+}
+
+fn get_instrument_names_from_song_id(db: &Connection, song_id: u64) -> Result<Vec<String>> {
+    let mut instruments = Vec::new();
+
+    let mut stmnt = db.prepare("SELECT text FROM instruments WHERE song_id = :song_id")?;
+    let mut rows = stmnt.query(&[(":song_id", &song_id)])?;
+
+    while let Some(row) = rows.next()? {
+        let text: String = row.get(0)?;
+        instruments.push(text);
+    }
+
+    Ok(instruments)
+}
+
+// --instrument-name-duplicates: groups files whose full instrument-name lists match once case
+// and surrounding whitespace are normalized away. The signature is built entirely in SQL
+// (GROUP_CONCAT preserves each song's instrument insertion order, which matches the order
+// instruments appear in the file) so this only needs one pass over the instruments table, rather
+// than calling get_instrument_names_from_song_id() per song and comparing in Rust. '\u{1f}' joins
+// the normalized names because it's not a character real instrument text is ever going to contain.
+fn get_instrument_name_dupes(db: &Connection, dupe_limit: usize) -> Result<Vec<Vec<DatabaseMeta>>> {
+    let mut stmnt = db.prepare(
+        "SELECT song_id, GROUP_CONCAT(LOWER(TRIM(text)), '\u{1f}') AS signature FROM instruments \
+         GROUP BY song_id HAVING signature IS NOT NULL AND signature != ''",
+    )?;
+    let mut rows = stmnt.query([])?;
+
+    let mut by_signature: HashMap<String, Vec<u64>> = HashMap::new();
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let signature: String = row.get(1)?;
+        by_signature.entry(signature).or_default().push(song_id);
+    }
+
+    let mut stmnt =
+        db.prepare("SELECT url, format, channel_count, file_size, sample_count FROM files WHERE song_id = ?")?;
+
+    let mut groups = Vec::new();
+
+    for song_ids in by_signature.values() {
+        if song_ids.len() <= dupe_limit {
+            continue;
+        }
+
+        let mut group = Vec::with_capacity(song_ids.len());
+
+        for song_id in song_ids {
+            let mut song_rows = stmnt.query(params![song_id])?;
+            if let Some(row) = song_rows.next()? {
+                group.push(DatabaseMeta {
+                    filename: row.get(0)?,
+                    samples: Vec::new(),
+                    instrument_names: get_instrument_names_from_song_id(db, *song_id)?,
+                    format: row.get(1)?,
+                    channel_count: row.get(2)?,
+                    file_size: row.get(3)?,
+                    sample_count: row.get(4)?,
+                });
+            }
+        }
+
+        groups.push(group);
+    }
+
+    Ok(groups)
+}
+
+// Cheap pre-filter for --quick: hashes the raw file bytes directly, without running it through
+// libopenmpt at all, and checks for a (file_size, hash_id) hit. A hit means the file is a sha256
+// match (hash_id is the same sha256 get_track_info() would have computed), so it can be reported
+// without paying for a full parse; a miss falls back to the normal get_track_info()-based path.
+fn quick_match_by_size_and_hash(filename: &str, db: &Connection) -> Result<Option<Vec<DatabaseMeta>>> {
+    let data = std::fs::read(filename)?;
+    let file_size = data.len() as u64;
+    let sha256_hash = format!("{:x}", sha2::Sha256::digest(&data));
+
+    let mut stmnt =
+        db.prepare("SELECT song_id, url FROM files WHERE file_size = :size AND hash_id = :hash")?;
+    let mut rows = stmnt.query(&[(":size", &file_size as &dyn rusqlite::ToSql), (":hash", &sha256_hash)])?;
+
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let url: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename: url, samples, ..Default::default() });
+    }
+
+    if entries.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(entries))
+    }
+}
+
+fn get_files_from_sha_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
+    let mut entries = Vec::new();
+
+    let mut stmnt = db.prepare("SELECT song_id, url FROM files WHERE hash_id = :hash")?;
+    let mut rows = stmnt.query(&[(":hash", &info.sha256_hash)])?;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename, samples, ..Default::default() });
+    }
+
+    Ok(entries)
+}
+
+// Matches on the combined hash of a module and its companion file (see compute_pair_hash), so
+// e.g. an MDX is only reported as a duplicate if its paired PDX matches too.
+fn get_files_from_pair_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
+    let mut entries = Vec::new();
+
+    let pair_hash = match info.pair_hash.as_ref() {
+        Some(hash) => hash,
+        None => return Ok(entries),
+    };
+
+    let mut stmnt = db.prepare("SELECT song_id, url FROM files WHERE pair_hash = :hash")?;
+    let mut rows = stmnt.query(&[(":hash", pair_hash)])?;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename, samples, ..Default::default() });
+    }
+
+    Ok(entries)
+}
+
+// Besides the pattern hash itself, also requires sample_count/instrument_count to match, so a
+// hash collision between two unrelated files of different formats (same 64-bit pattern hash,
+// completely different structure) doesn't get reported as a duplicate.
+fn get_files_from_pattern_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
+    let mut entries = Vec::new();
+
+    if info.pattern_hash == 0 {
+        return Ok(entries);
+    }
+
+    let pattern_hash = info.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+
+    let mut stmnt = db.prepare(
+        "SELECT song_id, url FROM files
+         WHERE pattern_hash = ?1 AND sample_count = ?2 AND instrument_count = ?3",
+    )?;
+    let mut rows = stmnt.query(params![pattern_hash, info.sample_count, info.instrument_count])?;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename, samples, ..Default::default() });
+    }
+
+    Ok(entries)
+}
+
+// Channel-order-invariant counterpart of get_files_from_pattern_hash(), used when
+// --match-normalized is set.
+fn get_files_from_normalized_pattern_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
+    let mut entries = Vec::new();
+
+    if info.normalized_pattern_hash == 0 {
+        return Ok(entries);
+    }
+
+    let normalized_pattern_hash = info.normalized_pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+
+    let mut stmnt = db.prepare(
+        "SELECT song_id, url FROM files
+         WHERE normalized_pattern_hash = ?1 AND sample_count = ?2 AND instrument_count = ?3",
+    )?;
+    let mut rows = stmnt.query(params![normalized_pattern_hash, info.sample_count, info.instrument_count])?;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename, samples, ..Default::default() });
+    }
+
+    Ok(entries)
+}
+
+// Patterns + raw sample data, see hash_canonical() in interface.cpp. More specific than
+// pattern_hash (it additionally requires sample data to be byte-identical) but less specific
+// than a full sha256 (the container bytes around that data are allowed to differ).
+fn get_files_from_canonical_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
+    let mut entries = Vec::new();
+
+    if info.canonical_hash == 0 {
+        return Ok(entries);
+    }
+
+    let canonical_hash = info.canonical_hash & 0x7FFF_FFFF_FFFF_FFFF;
+
+    let mut stmnt = db.prepare(
+        "SELECT song_id, url FROM files
+         WHERE canonical_hash = ?1 AND sample_count = ?2 AND instrument_count = ?3",
+    )?;
+    let mut rows = stmnt.query(params![canonical_hash, info.sample_count, info.instrument_count])?;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename, samples, ..Default::default() });
+    }
+
+    Ok(entries)
+}
+
+// Only meaningful when the local file was hashed with --render-fingerprint-seconds, since that's
+// the only thing that populates render_hash on either side of the comparison.
+fn get_files_from_render_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
+    let mut entries = Vec::new();
+
+    let render_hash = match info.render_hash {
+        Some(hash) => hash,
+        None => return Ok(entries),
+    };
+
+    let mut stmnt = db.prepare("SELECT song_id, url FROM files WHERE render_hash = :hash")?;
+    let mut rows = stmnt.query(&[(":hash", &render_hash)])?;
+
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let filename: String = row.get(1)?;
+        let samples = get_samples_from_song_id(db, song_id)?;
+
+        entries.push(DatabaseMeta { filename, samples, ..Default::default() });
+    }
+
+    Ok(entries)
+}
+
+fn print_samples_with_outline(samples: &[String], match_reg: &Option<Regex>, color: bool) {
+    if samples.is_empty() {
+        return;
+    }
+
+    // figure out the max len of the lines
+    let mut last_line_with_text = 0;
+    let mut max_len = 0;
+    for (index, line) in samples.iter().enumerate() {
+        max_len = std::cmp::max(line.chars().count(), max_len);
+        if !line.is_empty() {
+            last_line_with_text = index;
+        }
+    }
+
+    // spacing on each side
+    max_len += 2;
+
+    print!("┌");
+
+    for _in in 0..max_len {
+        print!("─");
+    }
+
+    println!("┐");
+
+    for (index, line) in samples.iter().enumerate() {
+        print!("│ ");
+        print!("{}", line);
+
+        for _ in line.chars().count()..max_len - 1 {
+            print!(" ");
+        }
+
+        if let Some(re) = match_reg.as_ref() {
+            if re.is_match(&line.to_ascii_lowercase()) {
+                let marker = colorize(&format!("<< regex ({}) match!", re.as_str()), "1;31", color);
+                println!("│ {}", marker);
+            } else {
+                println!("│");
+            }
+        } else {
+            println!("│");
+        }
+
+        if index == last_line_with_text {
+            break;
+        }
+    }
+
+    print!("└");
+    for _in in 0..max_len {
+        print!("─");
+    }
+
+    println!("┘");
+}
+
+fn print_found_entries(
+    inital_samples: &[String],
+    entries: &HashMap<&DatabaseMeta, (bool, bool)>,
+    args: &Args,
+    search_sample: &Option<Regex>,
+    source_filename: &str,
+    output: &mut OutputWriter,
+) -> Result<()> {
+    let mut printed_initial_samples = false;
+    let mut vals = Vec::with_capacity(entries.len());
+
+    for found in entries {
+        vals.push(found);
+    }
+
+    vals.sort_by(|a, b| a.0.filename.cmp(&b.0.filename));
+
+    let color = resolve_color(args, std::io::stdout().is_terminal());
+
+    for val in &vals {
+        let url = get_url(&val.0.filename);
+        if args.print_sample_names {
+            if !printed_initial_samples && args.print_sample_names {
+                print_samples_with_outline(inital_samples, search_sample, color);
+                printed_initial_samples = true;
+            }
+            output.record(
+                "pattern_hash",
+                source_filename,
+                &url,
+                &output.format_match_line("pattern_hash", &url),
+            )?;
+            print_samples_with_outline(&val.0.samples, search_sample, color);
+        } else if val.1 .0 && val.1 .1 {
+            output.record(
+                "hash+pattern_hash",
+                source_filename,
+                &url,
+                &output.format_match_line("hash+pattern_hash", &url),
+            )?;
+        } else if val.1 .0 && !val.1 .1 {
+            output.record(
+                "hash",
+                source_filename,
+                &url,
+                &output.format_match_line("hash", &url),
+            )?;
+        } else {
+            output.record(
+                "pattern_hash",
+                source_filename,
+                &url,
+                &output.format_match_line("pattern_hash", &url),
+            )?;
+        }
+    }
+
+    if vals.is_empty() && !args.quiet {
+        output.text_line("No matches found!")?;
+    }
+
+    Ok(())
+}
+
+// For files under `dir` that exactly match a database entry (same sha256), suggests renaming
+// the local file to the canonical modland filename (the basename of the matching database url),
+// and optionally writes a shell script of "mv" commands that perform the renames. Meant for
+// incoming uploads that arrive as something like "final2.mod" and need renaming to the
+// artist/title convention before they're filed into the mirror.
+fn suggest_names(dir: &str, args: &Args, db: &Connection) -> Result<()> {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        dir,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    let mut script_lines = vec!["#!/bin/sh".to_string()];
+    let mut suggested = 0;
+
+    for filename in files {
+        let info = get_track_info(
+            &filename,
+            args.dump_patterns,
+            false,
+            false,
+            args.render_fingerprint_seconds,
+            &args.sample_charset,
+            &errors,
+            args.fail_fast,
+        );
+
+        let hits = get_files_from_sha_hash(&info, db)?;
+        let Some(hit) = hits.first() else {
+            continue;
+        };
+
+        let canonical_name = Path::new(&hit.filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| hit.filename.clone());
+
+        let local_path = Path::new(&filename);
+        let current_name = local_path.file_name().and_then(|name| name.to_str());
+
+        if current_name == Some(canonical_name.as_str()) {
+            continue;
+        }
+
+        suggested += 1;
+
+        let new_path = local_path.with_file_name(&canonical_name);
+
+        println!("{} -> {}", filename, new_path.display());
+        println!("  (canonical: {})", get_url(&hit.filename));
+
+        script_lines.push(format!("mv -- {:?} {:?}", filename, new_path.display().to_string()));
+    }
+
+    if suggested == 0 {
+        println!("No rename suggestions found!");
+    }
+
+    if let Some(script_path) = args.rename_script.as_ref() {
+        script_lines.push(String::new());
+        std::fs::write(script_path, script_lines.join("\n"))?;
+        println!("\nWrote rename script to {}", script_path);
+    }
+
+    errors.finish();
+
+    Ok(())
+}
+
+// Guesses an "artist" folder name for --import's layout from a scanned file's immediate parent
+// directory under `src`, since that's how incoming dumps are usually already organized (e.g.
+// "incoming/Artist Name/song.mod"). Files sitting directly in `src` fall back to "Unknown"
+// rather than guessing at something that isn't there.
+fn guess_artist_dir(filename: &str, src: &str) -> String {
+    let relative = Path::new(filename).strip_prefix(src).unwrap_or_else(|_| Path::new(filename));
+    relative
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Unknown".to_owned())
+}
+
+// Copies every file under `src` that has no database match at all into `dest`, laid out as
+// "<format>/<artist>/<basename>", and logs the rest as skipped duplicates. This is --match-dir's
+// lookups reused for a one-shot copy instead of a report: a file counts as a duplicate if it
+// hits on sha256, pair hash, or a non-empty pattern hash. Unlike --match-dir, canonical-hash and
+// render-fingerprint hits are not checked.
+fn import_new_files(src: &str, dest: &str, args: &Args, db: &Connection) -> Result<()> {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        src,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    let mut imported = 0;
+    let mut skipped_duplicate = 0;
+
+    for filename in files {
+        let info = get_track_info(
+            &filename,
+            args.dump_patterns,
+            false,
+            false,
+            args.render_fingerprint_seconds,
+            &args.sample_charset,
+            &errors,
+            args.fail_fast,
+        );
+
+        let mut hits = get_files_from_sha_hash(&info, db)?;
+        hits.extend(get_files_from_pair_hash(&info, db)?);
+
+        let masked_pattern_hash = info.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+        if !info.is_empty_pattern && masked_pattern_hash != 0 {
+            hits.extend(get_files_from_pattern_hash(&info, db)?);
+        }
+
+        if let Some(hit) = hits.first() {
+            skipped_duplicate += 1;
+            if !args.quiet {
+                println!("Skipping {} (duplicate of {})", filename, get_url(&hit.filename));
+            }
+            continue;
+        }
+
+        let format = get_format_extension(&filename);
+        let artist = guess_artist_dir(&filename, src);
+        let basename = Path::new(&filename).file_name().unwrap_or_default();
+        let dest_dir = Path::new(dest).join(&format).join(&artist);
+        std::fs::create_dir_all(&dest_dir)?;
+
+        let dest_path = dest_dir.join(basename);
+        std::fs::copy(&filename, &dest_path)?;
+        imported += 1;
+
+        if !args.quiet {
+            println!("Imported {} -> {}", filename, dest_path.display());
+        }
+    }
+
+    println!("Imported {} file(s), skipped {} duplicate(s) already on the mirror", imported, skipped_duplicate);
+
+    errors.finish();
+
+    Ok(())
+}
+
+fn match_dir_against_db(dir: &str, args: &Args, db: &Connection) -> Result<()> {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        dir,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+    let mut found_match = false;
+    let mut output = OutputWriter::new(args)?;
+    let mut unmatched_files = Vec::new();
+    let bloom_filters = BloomFilters::load_optional(&get_bloom_filename());
+    let start_time = std::time::Instant::now();
+    let mut files_scanned = 0;
+    let mut exact_matches = 0;
+    let mut canonical_matches = 0;
+    let mut pattern_matches = 0;
+    let mut sample_matches = 0;
+    let mut empty_pattern_skipped = 0;
+
+    if let Some(staging_dir) = args.unmatched_staging_dir.as_ref() {
+        std::fs::create_dir_all(staging_dir)?;
+    }
+
+    //files.par_iter().for_each(|filename| {
+    for filename in files {
+        files_scanned += 1;
+
+        log::info!("Matching {}", filename);
+        if !args.quiet && !args.only_unmatched {
+            println!("Matching {}", filename);
+        }
+
+        if args.quick {
+            if let Some(hits) = quick_match_by_size_and_hash(&filename, db)? {
+                exact_matches += 1;
+                found_match = true;
+
+                if !args.only_unmatched {
+                    let mut found_entries = HashMap::new();
+                    for entry in &hits {
+                        found_entries.insert(entry, (true, false));
+                    }
+
+                    print_found_entries(&[], &found_entries, args, &filters.sample_search, &filename, &mut output)?;
+                    output.text_line("")?;
+                }
+                continue;
+            }
+        }
+
+        let info = get_track_info(
+            &filename,
+            args.dump_patterns,
+            false,
+            false,
+            args.render_fingerprint_seconds,
+            &args.sample_charset,
+            &errors,
+            args.fail_fast,
+        );
+
+        // Before touching SQLite at all, check the bloom filter side-file (if one was built
+        // alongside the database): a "definitely absent" result skips the query outright, which
+        // is the common case for a big scan of mostly-new material.
+        let maybe_has_hash_id = bloom_filters
+            .as_ref()
+            .is_none_or(|b| b.hash_id.contains(info.sha256_hash.as_bytes()));
+        let masked_pattern_hash = info.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+        let maybe_has_pattern_hash = args.match_normalized
+            || masked_pattern_hash == 0
+            || bloom_filters
+                .as_ref()
+                .is_none_or(|b| b.pattern_hash.contains(&masked_pattern_hash.to_le_bytes()));
+
+        let mut filenames = if maybe_has_hash_id {
+            get_files_from_sha_hash(&info, db)?
+        } else {
+            Vec::new()
+        };
+        filenames.extend(get_files_from_pair_hash(&info, db)?);
+        let filenames_canonical = get_files_from_canonical_hash(&info, db)?;
+        // A file with no orders or nothing but silent patterns hashes to the same degenerate
+        // pattern_hash as every other such file, so a pattern-hash-only query would just report
+        // it as "duplicated" against unrelated junk. Skipped by default; --include-empty-patterns
+        // opts back in.
+        let filenames_pattern_only = if info.is_empty_pattern && !args.include_empty_patterns {
+            if !args.quiet {
+                output.text_line("  (empty pattern, skipped as junk)")?;
+            }
+            empty_pattern_skipped += 1;
+            Vec::new()
+        } else if !maybe_has_pattern_hash {
+            Vec::new()
+        } else if args.match_normalized {
+            get_files_from_normalized_pattern_hash(&info, db)?
+        } else {
+            get_files_from_pattern_hash(&info, db)?
+        };
+        let filenames_render = get_files_from_render_hash(&info, db)?;
+        let mut filenames_pattern = filenames_canonical.clone();
+        filenames_pattern.extend(filenames_pattern_only.clone());
+        filenames_pattern.extend(filenames_render.clone());
+
+        if let Some(companion_url) = info.companion_url.as_ref() {
+            output.text_line(&format!("  (companion file: {})", get_url(companion_url)))?;
+        }
+
+        let filenames = filters.apply_filter(&filenames, 1);
+        let filenames_canonical = filters.apply_filter(&filenames_canonical, 1);
+        let filenames_pattern_only = filters.apply_filter(&filenames_pattern_only, 1);
+        let filenames_render = filters.apply_filter(&filenames_render, 1);
+        let filenames_pattern = filters.apply_filter(&filenames_pattern, 1);
+
+        // Tallied for the final summary: a file counts toward the most specific match kind it
+        // had (exact beats canonical beats pattern beats sample-only render fingerprint).
+        if !filenames.is_empty() {
+            exact_matches += 1;
+        } else if !filenames_canonical.is_empty() {
+            canonical_matches += 1;
+        } else if !filenames_pattern_only.is_empty() {
+            pattern_matches += 1;
+        } else if !filenames_render.is_empty() {
+            sample_matches += 1;
+        }
+
+        let mut found_entries = HashMap::new();
+
+        for entry in &filenames {
+            found_entries.insert(entry, (true, false));
+        }
+
+        for entry in &filenames_pattern {
+            if let Some(v) = found_entries.get_mut(entry) {
+                v.1 = true;
+            } else {
+                found_entries.insert(entry, (false, true));
+            }
+        }
+
+        if !found_entries.is_empty() {
+            found_match = true;
+        } else {
+            unmatched_files.push(filename.clone());
+        }
+
+        if args.only_unmatched {
+            if found_entries.is_empty() {
+                if let Some(staging_dir) = args.unmatched_staging_dir.as_ref() {
+                    let dest = Path::new(staging_dir).join(Path::new(&filename).file_name().unwrap_or_default());
+                    std::fs::copy(&filename, &dest)?;
+                }
+                output.text_line(&filename)?;
+            }
+        } else {
+            let sample_names: Vec<String> = info.samples.iter().map(|s| s.text.to_owned()).collect();
+
+            print_found_entries(
+                &sample_names,
+                &found_entries,
+                args,
+                &filters.sample_search,
+                &filename,
+                &mut output,
+            )?;
+
+            output.text_line("")?;
+        }
+    }
+
+    let elapsed_secs = start_time.elapsed().as_secs_f64();
+    let unmatched = unmatched_files.len();
+
+    output.write_summary(&MatchSummary {
+        files_scanned,
+        exact_matches,
+        canonical_matches,
+        pattern_matches,
+        sample_matches,
+        empty_pattern_skipped,
+        unmatched,
+        parse_failures: errors.count(),
+        elapsed_secs,
+        files_per_sec: if elapsed_secs > 0.0 {
+            files_scanned as f64 / elapsed_secs
+        } else {
+            0.0
+        },
+    })?;
+
+    output.finish()?;
+    errors.finish();
+
+    if let Some(playlist_path) = args.write_unmatched_playlist.as_ref() {
+        let mut playlist = String::from("#EXTM3U\n");
+        for filename in &unmatched_files {
+            playlist += filename;
+            playlist += "\n";
+        }
+        std::fs::write(playlist_path, playlist)?;
+        if !args.quiet {
+            println!("Wrote unmatched playlist ({} files) to {}", unmatched_files.len(), playlist_path);
+        }
+    }
+
+    if args.exit_code_on_match {
+        let code = if errors.has_errors() {
+            2
+        } else if found_match {
+            1
+        } else {
+            0
+        };
+        std::process::exit(code);
+    }
+
+    Ok(())
+}
+
+// Prints everything known about a single local file: its own hashes/metadata, plus every
+// database hit across all six match kinds (sha256, pair, canonical, pattern, normalized pattern
+// and render-fingerprint).
+// Unlike --match-dir, which only reports whether a file has duplicates, this is meant for
+// digging into why a specific file does or doesn't match something.
+fn inspect_file(filename: &str, args: &Args, db: &Connection) -> Result<()> {
+    let errors = RunErrors::new();
+    let info = get_track_info(
+        filename,
+        args.dump_patterns,
+        false,
+        false,
+        args.render_fingerprint_seconds,
+        &args.sample_charset,
+        &errors,
+        args.fail_fast,
+    );
+
+    println!("{}", filename);
+    println!("  parse_status:  {}", info.parse_status);
+    if let Some(parse_error) = info.parse_error.as_ref() {
+        println!("  parse_error:   {}", parse_error);
+    }
+    if let Some(warnings) = info.warnings.as_ref() {
+        for line in warnings.lines() {
+            println!("  warning:       {}", line);
+        }
+    }
+    println!("  format:        {}", info.format);
+    println!("  channel_count: {}", info.channel_count);
+    println!("  file_size:     {}", info.file_size);
+    println!("  sha256:        {}", info.sha256_hash);
+    println!("  pattern_hash:  {}", info.pattern_hash);
+    println!("  normalized_pattern_hash: {}", info.normalized_pattern_hash);
+    println!("  canonical_hash: {}", info.canonical_hash);
+    println!("  is_empty_pattern: {}", info.is_empty_pattern);
+
+    if let (Some(dir), Some(text)) = (args.dump_patterns_dir.as_ref(), info.pattern_text.as_ref()) {
+        write_pattern_dump(dir, &info.sha256_hash, text)?;
+        println!("  pattern_dump:  {}/{}.txt", dir, info.sha256_hash);
+    }
+
+    if let Some(render_hash) = info.render_hash {
+        println!("  render_hash:   {}", render_hash);
+    }
+
+    if let Some(pair_hash) = info.pair_hash.as_ref() {
+        println!("  pair_hash:     {}", pair_hash);
+    }
+
+    if let Some(companion_url) = info.companion_url.as_ref() {
+        println!("  companion:     {}", get_url(companion_url));
+    }
+
+    if info.samples.is_empty() {
+        println!("  samples:       (none)");
+    } else {
+        println!("  samples:");
+        for sample in &info.samples {
+            println!(
+                "    [{}] \"{}\" sha256={} length={} length_bytes={}",
+                sample.sample_id, sample.text, sample.sha256_hash, sample.length, sample.length_bytes
+            );
+            println!(
+                "         c5_speed={} pan={} volume={} global_vol={} stereo={} bits={} relative_tone={} fine_tune={} vibrato(type={} sweep={} depth={} rate={})",
+                sample.c5_speed,
+                sample.pan,
+                sample.volume,
+                sample.global_vol,
+                sample.stereo,
+                sample.bits_per_sample,
+                sample.relative_tone,
+                sample.fine_tune,
+                sample.vib_type,
+                sample.vib_sweep,
+                sample.vib_depth,
+                sample.vib_rate
+            );
+        }
+    }
+
+    if info.instrument_names.is_empty() {
+        println!("  instruments:   (none)");
+    } else {
+        println!("  instruments:");
+        for instrument in &info.instrument_names {
+            println!("    \"{}\"", instrument);
+        }
+    }
+
+    let sha_hits = get_files_from_sha_hash(&info, db)?;
+    let pair_hits = get_files_from_pair_hash(&info, db)?;
+    let canonical_hits = get_files_from_canonical_hash(&info, db)?;
+    let pattern_hits = get_files_from_pattern_hash(&info, db)?;
+    let normalized_hits = get_files_from_normalized_pattern_hash(&info, db)?;
+    let render_hits = get_files_from_render_hash(&info, db)?;
+
+    print_inspect_hits("sha256 hits", &sha_hits);
+    print_inspect_hits("pair hits", &pair_hits);
+    print_inspect_hits("canonical hits", &canonical_hits);
+    print_inspect_hits("pattern hits", &pattern_hits);
+    print_inspect_hits("normalized pattern hits", &normalized_hits);
+    print_inspect_hits("render hits", &render_hits);
+
+    errors.finish();
+
+    Ok(())
+}
+
+fn print_inspect_hits(label: &str, hits: &[DatabaseMeta]) {
+    if hits.is_empty() {
+        println!("  {}: (none)", label);
+        return;
+    }
+
+    println!("  {}:", label);
+    for hit in hits {
+        println!("    {}", get_url(&hit.filename));
+    }
+}
+
+#[derive(Clone)]
+struct MachingSampleData {
+    filename: String,
+    text: String,
+    text_lower: String,
+    sample_id: i64,
+    fine_tune: i64,
+    c5_speed: i64,
+}
+
+struct TopSampleData {
+    original_sample_id: i64,
+    text: String,
+    matching_samples: Vec<MachingSampleData>,
+}
+
+fn match_samples(dir: &str, db: &Connection, args: &Args) -> Result<()> {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let files = get_files(
+        dir,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
+
+    for filename in files {
+        let info = get_track_info(
+            &filename,
+            args.dump_patterns,
+            false,
+            false,
+            args.render_fingerprint_seconds,
+            &args.sample_charset,
+            &errors,
+            args.fail_fast,
+        );
+        let mut top_samples = Vec::new();
+        // Samples of this file that matched the same hash/fine_tune/c5_speed signature in some
+        // other database song, keyed by that song's url, so a per-file verdict can be printed
+        // once every sample's been checked, ranking candidates by how much of this file overlaps.
+        let mut verdict_samples: HashMap<String, HashSet<u32>> = HashMap::new();
+
+        if info.samples.is_empty() {
+            continue;
+        }
+
+        let mut max_len = 0;
+        for line in &info.samples {
+            max_len = std::cmp::max(line.text.chars().count(), max_len);
+        }
+
+        max_len += 2;
+
+        log::info!("Matching {} for duplicated samples", filename);
+        if !args.quiet {
+            println!("Matching {} for duplicated samples", filename);
+        }
+
+        // sha256_hash is a pre-quoted SQL literal (either "'<hex>'" or the bareword "NULL"), so
+        // strip the quotes to get the hash we can bind as a real parameter below.
+        let mut hashes_to_look_up = HashSet::new();
+        for sample in &info.samples {
+            if sample.length_bytes == 0 || sample.length_bytes < args.min_sample_length {
+                continue;
+            }
+            if let Some(hash) = sample.sha256_hash.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+                hashes_to_look_up.insert(hash.to_owned());
+            }
+        }
+
+        // One batched lookup per file instead of one per sample: besides the sample hash, also
+        // requires the owning file's channel/sample/instrument counts to match ours, so a sample
+        // hash collision between two otherwise unrelated files of different formats isn't
+        // reported as a duplicate.
+        let mut matches_by_hash: HashMap<String, Vec<MachingSampleData>> = HashMap::new();
+        if !hashes_to_look_up.is_empty() {
+            let placeholders = vec!["?"; hashes_to_look_up.len()].join(", ");
+            let statement = format!(
+                "SELECT samples.hash_id, song_sample_id, text, files.url, fine_tune, c5_speed
+                 FROM samples JOIN files ON samples.song_id = files.song_id
+                 WHERE samples.hash_id IN ({}) AND files.channel_count = ? AND files.sample_count = ? AND files.instrument_count = ?",
+                placeholders
+            );
+
+            let mut stmnt = db.prepare(&statement)?;
+            let mut query_params: Vec<&dyn rusqlite::ToSql> =
+                hashes_to_look_up.iter().map(|h| h as &dyn rusqlite::ToSql).collect();
+            query_params.push(&info.channel_count);
+            query_params.push(&info.sample_count);
+            query_params.push(&info.instrument_count);
+
+            let mut rows = stmnt.query(query_params.as_slice())?;
+
+            while let Some(row) = rows.next()? {
+                let hash_id: String = row.get(0)?;
+                let sample_id: i64 = row.get(1)?;
+                let text: String = row.get(2)?;
+                let url: String = row.get(3)?;
+                let fine_tune: i64 = row.get(4)?;
+                let c5_speed: i64 = row.get(5)?;
+                let text_lower = text.to_ascii_lowercase();
+
+                matches_by_hash.entry(hash_id).or_default().push(MachingSampleData {
+                    filename: url,
+                    text,
+                    text_lower,
+                    sample_id,
+                    fine_tune,
+                    c5_speed,
+                });
+            }
+        }
+
+        for sample in &info.samples {
+            if sample.length_bytes < args.min_sample_length {
+                continue;
+            }
+
+            let mut matching_data = sample
+                .sha256_hash
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .and_then(|hash| matches_by_hash.get(hash))
+                .cloned()
+                .unwrap_or_default();
+
+            if matching_data.len() < args.min_sample_dupes {
+                matching_data.clear();
+            }
+
+            for m in &matching_data {
+                verdict_samples.entry(m.filename.clone()).or_default().insert(sample.sample_id);
+            }
+
+            print!(
+                "{:02} {}",
+                sample.sample_id,
+                &sample.text[1..sample.text.len() - 1]
+            );
+
+            for _ in sample.text.chars().count()..max_len - 1 {
+                print!(" ");
+            }
+
+            let mut label_stmnt = db.prepare("SELECT label FROM annotations WHERE hash_id = ?1")?;
+            let mut label_rows = label_stmnt.query(params![sample.sha256_hash])?;
+            if let Some(row) = label_rows.next()? {
+                let label: String = row.get(0)?;
+                print!("[{}] ", label);
+            }
+
+            if !matching_data.is_empty() {
+                println!(
+                    "({} duplicates) length {}",
+                    matching_data.len(),
+                    sample.length
+                );
+
+                for m in &matching_data {
+                    if m.fine_tune != sample.fine_tune as i64 || m.c5_speed != sample.c5_speed as i64 {
+                        println!(
+                            "     ^ same data, different tuning in {} (fine_tune {} vs {}, c5_speed {} vs {})",
+                            m.filename, sample.fine_tune, m.fine_tune, sample.c5_speed, m.c5_speed
+                        );
+                    }
+                }
+            } else {
+                println!("length {}", sample.length);
+            }
+
+            if !matching_data.is_empty() {
+                matching_data.sort_by(|a, b| b.text_lower.cmp(&a.text_lower));
+
+                let t = TopSampleData {
+                    original_sample_id: sample.sample_id as _,
+                    text: sample.text.to_owned(),
+                    matching_samples: matching_data,
+                };
+
+                top_samples.push(t);
+            }
+        }
+
+        for i in top_samples {
+            println!(
+                "-------------------------------------------------------------------------------"
+            );
+            println!("{:02} {}", i.original_sample_id, i.text);
+            println!(
+                "-------------------------------------------------------------------------------"
+            );
+            let mut max_len = 0;
+            for m in &i.matching_samples {
+                max_len = std::cmp::max(m.text.chars().count(), max_len);
+            }
+
+            max_len += 2;
+
+            for m in &i.matching_samples {
+                print!("{:02} {}", m.sample_id, m.text);
+
+                for _ in m.text.chars().count()..max_len - 1 {
+                    print!(" ");
+                }
 
-        if read_size == 0 {
-            break;
+                println!("{}", m.filename);
+            }
         }
 
-        pb.set_position(pos);
-        pos += read_size as u64;
+        if !verdict_samples.is_empty() {
+            let total_samples = info.samples.len();
+            let mut ranked: Vec<(String, usize)> =
+                verdict_samples.into_iter().map(|(url, samples)| (url, samples.len())).collect();
+            ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
 
-        file.write_all(&temp_buffer[0..read_size])?;
+            println!(
+                "-------------------------------------------------------------------------------"
+            );
+            println!("Verdict for {}:", filename);
+            for (url, matched) in ranked {
+                println!("  {}/{} samples match {}", matched, total_samples, url);
+            }
+        }
     }
 
-    Ok(pb)
-}
+    errors.finish();
 
-fn decompress_db(pb: Option<ProgressBar>) -> Result<()> {
-    let filename = format!("{}.7z", get_db_filename());
+    Ok(())
+}
 
-    // Check if compressed file exists and unpack it
-    if !Path::new(&filename).exists() {
-        return Ok(());
+// First check if we have a database next to the to the exe, otherwise try local directory
+fn check_for_db_file() -> Option<PathBuf> {
+    let path = Path::new(&get_db_filename()).to_path_buf();
+    if path.exists() {
+        Some(path)
+    } else {
+        None
     }
+}
 
-    let mut sz = sevenz_rust::SevenZReader::open(&filename, "pass".into()).unwrap();
-    let total_size: u64 = sz
-        .archive()
-        .files
-        .iter()
-        .filter(|e| e.has_stream())
-        .map(|e| e.size())
-        .sum();
+fn get_dupes(
+    db: &Connection,
+    args: &Args,
+    get_songs_query: &str,
+    get_by_id: &str,
+    dupe_limit: usize,
+) -> Result<Vec<Vec<DatabaseMeta>>> {
+    let mut hash_dupes = Vec::with_capacity(700_0000);
+    let filters = Filters::new(args);
 
-    let pb = if let Some(pb) = pb {
-        pb.set_length(total_size as _);
-        pb
-    } else {
-        create_progress_bar(total_size as _)
-    };
+    let mut stmnt = db.prepare(get_songs_query)?;
+    let mut rows = stmnt.query([])?;
 
-    pb.set_prefix("Decompressing Database");
+    let mut stmnt = db.prepare(get_by_id)?;
+    let mut hash_id_lookup_string = HashSet::new();
+    let mut hash_id_lookup_int = HashSet::new();
 
-    let mut uncompressed_size = 0;
-    sz.for_each_entries(|_entry, reader| {
-        let mut buf = [0u8; 1024];
-        let mut file = File::create(get_db_filename()).unwrap();
-        loop {
-            let read_size = reader.read(&mut buf).unwrap();
-            if read_size == 0 {
-                break Ok(true);
+    while let Some(row) = rows.next()? {
+        let v = row.get_ref(0)?;
+        let mut vals = Vec::with_capacity(10);
+        let mut song_ids = Vec::with_capacity(10);
+
+        let mut song_rows = match v {
+            ValueRef::Null => continue,
+            ValueRef::Integer(v) => {
+                let v = v as u64;
+                if let Some(_v) = hash_id_lookup_int.get(&v) {
+                    continue;
+                } else {
+                    hash_id_lookup_int.insert(v);
+                }
+
+                stmnt.query(params![v])?
+            }
+
+            ValueRef::Text(v) => {
+                let v = std::str::from_utf8(v)?.to_owned();
+
+                if let Some(_v) = hash_id_lookup_string.get(&v) {
+                    continue;
+                } else {
+                    hash_id_lookup_string.insert(v.clone());
+                }
+
+                stmnt.query(params![v])?
             }
-            file.write_all(&buf[..read_size])?;
-            uncompressed_size += read_size;
 
-            pb.set_position(uncompressed_size as _);
+            _ => panic!(),
+        };
+
+        while let Some(row) = song_rows.next()? {
+            let song_id: u64 = row.get(0)?;
+            let filename: String = row.get(1)?;
+            let format: String = row.get(2)?;
+            let channel_count: u32 = row.get(3)?;
+            let file_size: u64 = row.get(4)?;
+            let sample_count: u32 = row.get(5)?;
+            let metadata = DatabaseMeta {
+                filename,
+                samples: Vec::new(),
+                instrument_names: Vec::new(),
+                format,
+                channel_count,
+                file_size,
+                sample_count,
+            };
+            vals.push(metadata);
+            song_ids.push(song_id);
         }
-    })
-    .unwrap();
 
-    // delete the compressed file
-    std::fs::remove_file(&filename)?;
+        if vals.len() <= dupe_limit {
+            continue;
+        }
 
-    Ok(())
-}
+        if args.max_dupes.is_some_and(|max| vals.len() > max) {
+            continue;
+        }
 
-/*
-    let re = Regex::new(search_string).unwrap();
-    let mut count = 0;
+        if filters.sample_search.is_some() || args.print_sample_names || args.duplicates_json {
+            for (metadata, song_id) in vals.iter_mut().zip(song_ids.iter()) {
+                let t = get_samples_from_song_id(db, *song_id)?;
+                metadata.samples = t;
+            }
+        }
 
-    tracks.iter().for_each(|track| {
-        if let Some(metadata) = track.metadata.as_ref() {
-            if re.is_match(&metadata.sample_names.to_ascii_lowercase()) {
-                println!("===============================================================");
-                println!("Matching {}", track.filename);
-                println!("{}", metadata.sample_names);
-                count += 1;
+        if filters.instrument_search.is_some() || args.print_instrument_names {
+            for (metadata, song_id) in vals.iter_mut().zip(song_ids.iter()) {
+                let t = get_instrument_names_from_song_id(db, *song_id)?;
+                metadata.instrument_names = t;
             }
         }
-    });
 
-    println!("Total matches {}", count);
-}
-     */
+        let mut vals = filters.apply_filter(&vals, dupe_limit + 1);
 
-fn get_samples_from_song_id(db: &Connection, song_id: u64) -> Result<Vec<String>> {
-    let mut samples = Vec::new();
+        if !vals.is_empty() {
+            vals.sort_by(|a, b| a.filename.cmp(&b.filename));
+            hash_dupes.push(vals);
+        }
+    }
 
-    let mut stmnt = db.prepare("SELECT text FROM samples WHERE song_id = :song_id")?;
-    let mut rows = stmnt.query(&[(":song_id", &song_id)])?;
+    sort_dupe_groups(&mut hash_dupes, &args.sort);
 
-    while let Some(row) = rows.next()? {
-        let text: String = row.get(0)?;
-        samples.push(text);
+    if let Some(limit) = args.limit {
+        hash_dupes.truncate(limit);
     }
 
-    Ok(samples)
+    Ok(hash_dupes)
 }
 
-fn get_files_from_sha_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
-    let mut entries = Vec::new();
+// The last path segment of a database filename (e.g. "/pub/modules/x/y.mod" -> "y.mod"), used
+// for --sort name so groups order by filename rather than full path.
+fn basename(filename: &str) -> &str {
+    filename.rsplit('/').next().unwrap_or(filename)
+}
 
-    let mut stmnt = db.prepare("SELECT song_id, url FROM files WHERE hash_id = :hash")?;
-    let mut rows = stmnt.query(&[(":hash", &info.sha256_hash)])?;
+// Orders duplicate groups for --sort: "path" (default, full path of the first entry), "name"
+// (basename of the first entry), "size" (total file_size in the group, biggest first) or
+// "dupes" (entry count, most duplicated first).
+fn sort_dupe_groups(groups: &mut [Vec<DatabaseMeta>], sort: &str) {
+    match sort {
+        "name" => groups.sort_by(|a, b| basename(&a[0].filename).cmp(basename(&b[0].filename))),
+        "size" => groups.sort_by(|a, b| {
+            let size_a: u64 = a.iter().map(|e| e.file_size).sum();
+            let size_b: u64 = b.iter().map(|e| e.file_size).sum();
+            size_b.cmp(&size_a)
+        }),
+        "dupes" => groups.sort_by_key(|g| std::cmp::Reverse(g.len())),
+        _ => groups.sort_by(|a, b| a[0].filename.cmp(&b[0].filename)),
+    }
+}
 
-    while let Some(row) = rows.next()? {
-        let song_id: u64 = row.get(0)?;
-        let filename: String = row.get(1)?;
-        let samples = get_samples_from_song_id(db, song_id)?;
+// Final tally for a --match-dir run, printed by OutputWriter::write_summary().
+struct MatchSummary {
+    files_scanned: usize,
+    exact_matches: usize,
+    canonical_matches: usize,
+    pattern_matches: usize,
+    sample_matches: usize,
+    empty_pattern_skipped: usize,
+    unmatched: usize,
+    parse_failures: usize,
+    elapsed_secs: f64,
+    files_per_sec: f64,
+}
 
-        entries.push(DatabaseMeta { filename, samples });
+// Resolves --color against the given terminal-ness of the target stream: "always"/"never"
+// are absolute, "auto" additionally honors NO_COLOR (https://no-color.org).
+fn resolve_color(args: &Args, is_terminal: bool) -> bool {
+    match args.color.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => is_terminal && std::env::var_os("NO_COLOR").is_none(),
     }
+}
 
-    Ok(entries)
+// Wraps `text` in the given SGR color code if `enabled`, otherwise returns it unchanged.
+fn colorize(text: &str, code: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\u{1b}[{}m{}\u{1b}[0m", code, text)
+    } else {
+        text.to_string()
+    }
 }
 
-fn get_files_from_pattern_hash(info: &TrackInfo, db: &Connection) -> Result<Vec<DatabaseMeta>> {
-    let mut entries = Vec::new();
+// Where a duplicate listing or match run's results go: stdout by default, or --output-file in
+// the chosen --output-format. Keeps progress-bar output (which writes its own escape codes to
+// stderr) separate from the actual results so a redirected/piped run's output stays clean.
+enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Html,
+}
 
-    if info.pattern_hash == 0 {
-        return Ok(entries);
-    }
+struct OutputWriter {
+    format: OutputFormat,
+    writer: Box<dyn Write>,
+    wrote_record: bool,
+    // Only populated for Html, since the report is rendered as one document at `finish()` time
+    // (grouped by `context`) rather than streamed line by line like the other formats.
+    html_records: Vec<(String, String, String)>,
+    // Colorizes "text" output; never set for the other formats, which are consumed by tools
+    // rather than read in a terminal.
+    color: bool,
+}
 
-    let pattern_hash = info.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF;
+impl OutputWriter {
+    fn new(args: &Args) -> Result<OutputWriter> {
+        let format = match args.output_format.as_str() {
+            "json" => OutputFormat::Json,
+            "csv" => OutputFormat::Csv,
+            "html" => OutputFormat::Html,
+            _ => OutputFormat::Text,
+        };
 
-    let mut stmnt = db.prepare("SELECT song_id, url FROM files WHERE pattern_hash = :hash")?;
-    let mut rows = stmnt.query(&[(":hash", &pattern_hash)])?;
+        let is_terminal = args.output_file.is_none() && std::io::stdout().is_terminal();
+        let color = matches!(format, OutputFormat::Text) && resolve_color(args, is_terminal);
 
-    while let Some(row) = rows.next()? {
-        let song_id: u64 = row.get(0)?;
-        let filename: String = row.get(1)?;
-        let samples = get_samples_from_song_id(db, song_id)?;
+        let writer: Box<dyn Write> = match args.output_file.as_ref() {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
 
-        entries.push(DatabaseMeta { filename, samples });
-    }
+        let mut output = OutputWriter {
+            format,
+            writer,
+            wrote_record: false,
+            html_records: Vec::new(),
+            color,
+        };
 
-    Ok(entries)
-}
+        match output.format {
+            OutputFormat::Json => writeln!(output.writer, "[")?,
+            OutputFormat::Csv => writeln!(output.writer, "kind,context,url")?,
+            OutputFormat::Text | OutputFormat::Html => {}
+        }
 
-fn print_samples_with_outline(samples: &[String], match_reg: &Option<Regex>) {
-    if samples.is_empty() {
-        return;
+        Ok(output)
     }
 
-    // figure out the max len of the lines
-    let mut last_line_with_text = 0;
-    let mut max_len = 0;
-    for (index, line) in samples.iter().enumerate() {
-        max_len = std::cmp::max(line.chars().count(), max_len);
-        if !line.is_empty() {
-            last_line_with_text = index;
+    // A free-text line (section header, separator); only emitted for the "text" format since it
+    // doesn't map to a structured record.
+    fn text_line(&mut self, line: &str) -> Result<()> {
+        if let OutputFormat::Text = self.format {
+            writeln!(self.writer, "{}", line)?;
         }
+        Ok(())
     }
 
-    // spacing on each side
-    max_len += 2;
-
-    print!("┌");
+    // Colorizes a group separator/header line ("====...", "Dupe Entry 3 (hash)") for the "text"
+    // format; a no-op (besides the text_line plumbing) everywhere else.
+    fn header_line(&mut self, line: &str) -> Result<()> {
+        self.text_line(&colorize(line, "1;34", self.color))
+    }
 
-    for _in in 0..max_len {
-        print!("─");
+    // Builds the "<TAG> Found match <url>" line with a fixed-width, colorized kind tag so
+    // entries line up in a column regardless of url length.
+    fn format_match_line(&self, kind: &str, url: &str) -> String {
+        let (tag, code) = match kind {
+            "hash+pattern_hash" => ("[HASH+PATTERN]", "36"),
+            "hash" => ("[HASH]        ", "32"),
+            _ => ("[PATTERN]     ", "33"),
+        };
+        format!("{} Found match {}", colorize(tag, code, self.color), url)
     }
 
-    println!("┐");
+    // A single matched/duplicate entry. `context` is the group/source this entry was found
+    // under (e.g. "Dupe Entry 3 (hash)", or the file being matched against the database).
+    // `text_line` is the exact line to print for the "text" format, which keeps its own,
+    // more verbose phrasing (e.g. "[HASH] Found match <url>") instead of a bare URL.
+    fn record(&mut self, kind: &str, context: &str, url: &str, text_line: &str) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => writeln!(self.writer, "{}", text_line)?,
+            OutputFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{}",
+                kind,
+                context.replace(',', " "),
+                url
+            )?,
+            OutputFormat::Json => {
+                if self.wrote_record {
+                    writeln!(self.writer, ",")?;
+                }
+                write!(
+                    self.writer,
+                    "  {{\"kind\": \"{}\", \"context\": \"{}\", \"url\": \"{}\"}}",
+                    kind,
+                    context.replace('"', "\\\""),
+                    url.replace('"', "\\\"")
+                )?;
+                self.wrote_record = true;
+            }
+            OutputFormat::Html => {
+                self.html_records
+                    .push((kind.to_string(), context.to_string(), url.to_string()));
+            }
+        }
 
-    for (index, line) in samples.iter().enumerate() {
-        print!("│ ");
-        print!("{}", line);
+        Ok(())
+    }
 
-        for _ in line.chars().count()..max_len - 1 {
-            print!(" ");
+    fn finish(&mut self) -> Result<()> {
+        match self.format {
+            OutputFormat::Json => {
+                writeln!(self.writer)?;
+                writeln!(self.writer, "]")?;
+            }
+            OutputFormat::Html => self.write_html_report()?,
+            OutputFormat::Text | OutputFormat::Csv => {}
         }
+        Ok(())
+    }
 
-        if let Some(re) = match_reg.as_ref() {
-            if re.is_match(&line.to_ascii_lowercase()) {
-                println!("│ << regex ({}) match!", re.as_str());
-            } else {
-                println!("│");
+    // Final tally for a --match-dir run: printed as a block for "text", folded into the JSON
+    // array as one last "kind": "summary" record for "json". Not emitted for "csv"/"html",
+    // whose fixed record shape (kind,context,url) has no room for it.
+    fn write_summary(&mut self, summary: &MatchSummary) -> Result<()> {
+        match self.format {
+            OutputFormat::Text => {
+                writeln!(self.writer, "== Summary ==")?;
+                writeln!(self.writer, "Files scanned:   {}", summary.files_scanned)?;
+                writeln!(self.writer, "Exact matches:   {}", summary.exact_matches)?;
+                writeln!(self.writer, "Canonical matches: {}", summary.canonical_matches)?;
+                writeln!(self.writer, "Pattern matches: {}", summary.pattern_matches)?;
+                writeln!(self.writer, "Sample matches:  {}", summary.sample_matches)?;
+                writeln!(self.writer, "Empty pattern (skipped as junk): {}", summary.empty_pattern_skipped)?;
+                writeln!(self.writer, "Unmatched:       {}", summary.unmatched)?;
+                writeln!(self.writer, "Parse failures:  {}", summary.parse_failures)?;
+                writeln!(
+                    self.writer,
+                    "Elapsed:         {:.2}s ({:.2} files/sec)",
+                    summary.elapsed_secs, summary.files_per_sec
+                )?;
             }
-        } else {
-            println!("│");
+            OutputFormat::Json => {
+                if self.wrote_record {
+                    writeln!(self.writer, ",")?;
+                }
+                write!(
+                    self.writer,
+                    "  {{\"kind\": \"summary\", \"files_scanned\": {}, \"exact_matches\": {}, \
+                     \"canonical_matches\": {}, \"pattern_matches\": {}, \"sample_matches\": {}, \
+                     \"empty_pattern_skipped\": {}, \"unmatched\": {}, \"parse_failures\": {}, \
+                     \"elapsed_secs\": {:.3}, \"files_per_sec\": {:.3}}}",
+                    summary.files_scanned,
+                    summary.exact_matches,
+                    summary.canonical_matches,
+                    summary.pattern_matches,
+                    summary.sample_matches,
+                    summary.empty_pattern_skipped,
+                    summary.unmatched,
+                    summary.parse_failures,
+                    summary.elapsed_secs,
+                    summary.files_per_sec
+                )?;
+                self.wrote_record = true;
+            }
+            OutputFormat::Csv | OutputFormat::Html => {}
         }
+        Ok(())
+    }
 
-        if index == last_line_with_text {
-            break;
+    // Renders all records collected via `record()` into one self-contained HTML report: a
+    // search box filters visible rows by substring (url, context or kind), and each context
+    // (duplicate group or matched source file) is a collapsible <details> box so a large run
+    // doesn't render as one unreadable wall of links.
+    fn write_html_report(&mut self) -> Result<()> {
+        type HtmlRecord = (String, String, String);
+        let mut groups: Vec<(&str, Vec<&HtmlRecord>)> = Vec::new();
+        for record in &self.html_records {
+            match groups.iter_mut().find(|(ctx, _)| *ctx == record.1) {
+                Some((_, entries)) => entries.push(record),
+                None => groups.push((&record.1, vec![record])),
+            }
         }
-    }
 
-    print!("└");
-    for _in in 0..max_len {
-        print!("─");
-    }
+        writeln!(self.writer, "<!DOCTYPE html>")?;
+        writeln!(self.writer, "<html lang=\"en\"><head><meta charset=\"utf-8\">")?;
+        writeln!(self.writer, "<title>modland_hash report</title><style>")?;
+        writeln!(
+            self.writer,
+            "body {{ font-family: monospace; background: #1e1e1e; color: #ddd; }}\n\
+             input#search {{ width: 100%; padding: 6px; font-size: 1rem; margin-bottom: 1em; }}\n\
+             details {{ border: 1px solid #444; margin-bottom: 6px; padding: 4px 8px; }}\n\
+             summary {{ cursor: pointer; font-weight: bold; }}\n\
+             li.hidden {{ display: none; }}\n\
+             a {{ color: #6cf; }}"
+        )?;
+        writeln!(self.writer, "</style></head><body>")?;
+        writeln!(self.writer, "<h1>modland_hash report</h1>")?;
+        writeln!(
+            self.writer,
+            "<input id=\"search\" type=\"text\" placeholder=\"Filter by url, context or kind\u{2026}\" \
+             oninput=\"filterReport(this.value)\">"
+        )?;
+
+        for (context, entries) in &groups {
+            writeln!(self.writer, "<details open>")?;
+            writeln!(
+                self.writer,
+                "<summary>{} ({})</summary><ul>",
+                html_escape(context),
+                entries.len()
+            )?;
+            for (kind, _, url) in entries {
+                writeln!(
+                    self.writer,
+                    "<li data-text=\"{} {} {}\"><a href=\"{}\">{}</a> <small>({})</small></li>",
+                    html_escape(context).to_ascii_lowercase(),
+                    html_escape(kind).to_ascii_lowercase(),
+                    html_escape(url).to_ascii_lowercase(),
+                    html_escape(url),
+                    html_escape(url),
+                    html_escape(kind)
+                )?;
+            }
+            writeln!(self.writer, "</ul></details>")?;
+        }
 
-    println!("┘");
+        writeln!(
+            self.writer,
+            "<script>
+function filterReport(needle) {{
+    needle = needle.toLowerCase();
+    document.querySelectorAll('li[data-text]').forEach(function(li) {{
+        li.classList.toggle('hidden', needle !== '' && li.dataset.text.indexOf(needle) === -1);
+    }});
+}}
+</script>"
+        )?;
+        writeln!(self.writer, "</body></html>")?;
+
+        Ok(())
+    }
 }
 
-fn print_found_entries(
-    inital_samples: &[String],
-    entries: &HashMap<&DatabaseMeta, (bool, bool)>,
-    args: &Args,
-    search_sample: &Option<Regex>,
-) {
-    let mut printed_initial_samples = false;
-    let mut vals = Vec::with_capacity(entries.len());
+// Minimal HTML-entity escaping for the handful of characters that can appear in urls/contexts
+// (filenames, modland paths) and would otherwise break attribute/text parsing.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
 
-    for found in entries {
-        vals.push(found);
-    }
+fn json_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
 
-    vals.sort_by(|a, b| a.0.filename.cmp(&b.0.filename));
+// One line per group: --duplicates-json's alternative to print_db_duplicates()'s decorated text
+// report, for cleanup scripts that want groups, not lines, and shouldn't have to reassemble them
+// from a flat kind/context/url stream.
+fn print_db_duplicates_json(groups: &[Vec<DatabaseMeta>], kind: &str) -> Result<()> {
+    for group in groups {
+        let members: Vec<String> = group
+            .iter()
+            .map(|e| {
+                let samples: Vec<String> =
+                    e.samples.iter().map(|s| format!("\"{}\"", json_escape(s))).collect();
+                format!(
+                    "{{\"url\": \"{}\", \"format\": \"{}\", \"channel_count\": {}, \"file_size\": {}, \"samples\": [{}]}}",
+                    json_escape(&get_url(&e.filename)),
+                    json_escape(&e.format),
+                    e.channel_count,
+                    e.file_size,
+                    samples.join(", ")
+                )
+            })
+            .collect();
 
-    for val in &vals {
-        let url = get_url(&val.0.filename);
-        if args.print_sample_names {
-            if !printed_initial_samples && args.print_sample_names {
-                print_samples_with_outline(inital_samples, search_sample);
-                printed_initial_samples = true;
-            }
-            println!("Found match {} (pattern_hash)", url);
-            print_samples_with_outline(&val.0.samples, search_sample);
-        } else if val.1 .0 && val.1 .1 {
-            println!("Found match {} (hash) (pattern_hash)", url);
-        } else if val.1 .0 && !val.1 .1 {
-            println!("Found match {} (hash)", url);
-        } else {
-            println!("Found match {} (pattern_hash)", url);
-        }
+        println!(
+            "{{\"kind\": \"{}\", \"members\": [{}]}}",
+            kind,
+            members.join(", ")
+        );
     }
 
-    if vals.is_empty() {
-        println!("No matches found!");
-    }
+    Ok(())
 }
 
-fn match_dir_against_db(dir: &str, args: &Args, db: &Connection) -> Result<()> {
-    let files = get_files(dir, args.recursive);
+// Comparison key for --cross-dir-only: the last `depth` components of the directory containing
+// `filename`, joined back together. depth 1 (the default) is just the immediate parent directory;
+// a larger depth lets e.g. "composer/album" count as one "directory" instead of just "album".
+fn dir_key(filename: &str, depth: usize) -> String {
+    let parent = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+    let components: Vec<_> = parent.components().collect();
+    let start = components.len().saturating_sub(depth.max(1));
+
+    components[start..]
+        .iter()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// --cross-dir-only: drops groups where every member resolves to the same dir_key(), since those
+// are usually just a mirror re-listing one song under itself rather than the cross-section dupes
+// this flag is meant to surface.
+fn retain_cross_dir_groups(groups: Vec<Vec<DatabaseMeta>>, depth: usize) -> Vec<Vec<DatabaseMeta>> {
+    groups
+        .into_iter()
+        .filter(|group| {
+            let mut keys = group.iter().map(|e| dir_key(&e.filename, depth));
+            let first = keys.next();
+            first.is_some_and(|first| keys.any(|k| k != first))
+        })
+        .collect()
+}
+
+fn print_db_duplicates(db: &Connection, args: &Args) -> Result<()> {
     let filters = Filters::new(args);
 
-    //files.par_iter().for_each(|filename| {
-    for filename in files {
-        let info = get_track_info(&filename, args.dump_patterns);
+    let hash_dupes = get_dupes(
+        db,
+        args,
+        "SELECT hash_id FROM files",
+        "SELECT song_id, url, format, channel_count, file_size, sample_count FROM files where hash_id = ?",
+        args.min_dupes.saturating_sub(1),
+    )?;
 
-        println!("Matching {}", filename);
+    let pattern_query = if args.include_empty_patterns {
+        "SELECT pattern_hash FROM files"
+    } else {
+        "SELECT pattern_hash FROM files WHERE is_empty_pattern IS NOT 1"
+    };
 
-        let filenames = get_files_from_sha_hash(&info, db)?;
-        let filenames_pattern = get_files_from_pattern_hash(&info, db)?;
+    let pattern_dupes = get_dupes(
+        db,
+        args,
+        pattern_query,
+        "SELECT song_id, url, format, channel_count, file_size, sample_count FROM files where pattern_hash = ?",
+        args.min_dupes.saturating_sub(1),
+    )?;
 
-        let filenames = filters.apply_filter(&filenames, 1);
-        let filenames_pattern = filters.apply_filter(&filenames_pattern, 1);
+    let instrument_name_dupes = if args.instrument_name_duplicates {
+        get_instrument_name_dupes(db, args.min_dupes.saturating_sub(1))?
+    } else {
+        Vec::new()
+    };
 
-        let mut found_entries = HashMap::new();
+    let (hash_dupes, pattern_dupes, instrument_name_dupes) = if let Some(depth) = args.cross_dir_only {
+        (
+            retain_cross_dir_groups(hash_dupes, depth),
+            retain_cross_dir_groups(pattern_dupes, depth),
+            retain_cross_dir_groups(instrument_name_dupes, depth),
+        )
+    } else {
+        (hash_dupes, pattern_dupes, instrument_name_dupes)
+    };
 
-        for entry in &filenames {
-            found_entries.insert(entry, (true, false));
-        }
+    let (hash_dupes, pattern_dupes, instrument_name_dupes) = if let Some(canonical_path) = args.canonical_path.as_ref()
+    {
+        (
+            retain_candidate_groups(hash_dupes, canonical_path),
+            retain_candidate_groups(pattern_dupes, canonical_path),
+            retain_candidate_groups(instrument_name_dupes, canonical_path),
+        )
+    } else {
+        (hash_dupes, pattern_dupes, instrument_name_dupes)
+    };
 
-        for entry in &filenames_pattern {
-            if let Some(v) = found_entries.get_mut(entry) {
-                v.1 = true;
-            } else {
-                found_entries.insert(entry, (false, true));
-            }
-        }
+    if args.duplicates_json {
+        print_db_duplicates_json(&hash_dupes, "hash")?;
+        print_db_duplicates_json(&pattern_dupes, "pattern_hash")?;
+        print_db_duplicates_json(&instrument_name_dupes, "instrument_names")?;
+        return Ok(());
+    }
 
-        let sample_names: Vec<String> = info.samples.iter().map(|s| s.text.to_owned()).collect();
+    let mut output = OutputWriter::new(args)?;
+    let color = resolve_color(args, std::io::stdout().is_terminal());
+
+    for (index, v) in hash_dupes.iter().enumerate() {
+        print_dupe_group(&mut output, &filters, color, args, "hash", index, v, false)?;
+    }
 
-        print_found_entries(&sample_names, &found_entries, args, &filters.sample_search);
+    for (index, v) in pattern_dupes.iter().enumerate() {
+        print_dupe_group(&mut output, &filters, color, args, "pattern_hash", index, v, false)?;
+    }
 
-        println!();
+    for (index, v) in instrument_name_dupes.iter().enumerate() {
+        print_dupe_group(&mut output, &filters, color, args, "instrument_names", index, v, true)?;
     }
 
+    output.finish()?;
+
     Ok(())
 }
 
-struct MachingSampleData {
-    filename: String,
-    text: String,
-    text_lower: String,
-    sample_id: i64,
+// --canonical-path: drops groups that are entirely canonical, since those are just a mirror
+// listing itself under its own curated tree rather than a dupe worth reporting.
+fn retain_candidate_groups(groups: Vec<Vec<DatabaseMeta>>, canonical_path: &str) -> Vec<Vec<DatabaseMeta>> {
+    groups.into_iter().filter(|group| group.iter().any(|e| !e.filename.starts_with(canonical_path))).collect()
 }
 
-struct TopSampleData {
-    original_sample_id: i64,
-    text: String,
-    matching_samples: Vec<MachingSampleData>,
-}
+// Shared renderer for hash_dupes/pattern_dupes/instrument_name_dupes' per-group output.
+// force_instrument_names is set for instrument_name_dupes, where the instrument list is the
+// whole point of the group and should print regardless of --print-instrument-names. With
+// --canonical-path, each non-canonical ("candidate") entry is phrased as duplicating every
+// canonical entry in the group instead of the default flat entry list; group selection (dropping
+// all-canonical groups) already happened in retain_candidate_groups().
+#[allow(clippy::too_many_arguments)]
+fn print_dupe_group(
+    output: &mut OutputWriter,
+    filters: &Filters,
+    color: bool,
+    args: &Args,
+    kind: &str,
+    index: usize,
+    group: &[DatabaseMeta],
+    force_instrument_names: bool,
+) -> Result<()> {
+    let context = format!("Dupe Entry {} ({})", index, kind);
+    output.header_line("\n==================================================================")?;
+    output.header_line(&context)?;
+
+    let canonical_path = args.canonical_path.as_ref();
+    let canonical: Vec<&DatabaseMeta> =
+        canonical_path.map(|p| group.iter().filter(|e| e.filename.starts_with(p.as_str())).collect()).unwrap_or_default();
+
+    for e in group {
+        if canonical_path.is_some_and(|p| e.filename.starts_with(p.as_str())) {
+            continue;
+        }
 
-fn match_samples(dir: &str, db: &Connection, args: &Args) -> Result<()> {
-    let files = get_files(dir, args.recursive);
+        let candidate_url = get_url(&e.filename);
 
-    for filename in files {
-        let info = get_track_info(&filename, args.dump_patterns);
-        let mut top_samples = Vec::new();
+        if canonical.is_empty() {
+            output.record(kind, &context, &candidate_url, &candidate_url)?;
+        } else {
+            for canon in &canonical {
+                let line = format!("candidate {} duplicates canonical {}", candidate_url, get_url(&canon.filename));
+                output.record(kind, &context, &candidate_url, &line)?;
+            }
+        }
 
-        if info.samples.is_empty() {
-            continue;
+        if filters.sample_search.is_some() || args.print_sample_names {
+            print_samples_with_outline(&e.samples, &filters.sample_search, color);
         }
 
-        let mut max_len = 0;
-        for line in &info.samples {
-            max_len = std::cmp::max(line.text.chars().count(), max_len);
+        if force_instrument_names || filters.instrument_search.is_some() || args.print_instrument_names {
+            print_samples_with_outline(&e.instrument_names, &filters.instrument_search, color);
         }
+    }
 
-        max_len += 2;
+    Ok(())
+}
 
-        println!("Matching {} for duplicated samples", filename);
+fn print_db(db: &Connection, args: &Args) -> Result<()> {
+    let filters = Filters::new(args);
+    let color = resolve_color(args, std::io::stdout().is_terminal());
 
-        for sample in &info.samples {
-            let mut matching_data = Vec::new();
-
-            if sample.length_bytes > 0 {
-                let statement = format!("
-                    SELECT song_sample_id, text, files.url 
-                    FROM samples JOIN files ON samples.song_id = files.song_id WHERE samples.hash_id = {}",
-                    sample.sha256_hash);
-
-                let mut stmnt = db.prepare(&statement)?;
-                let mut rows = stmnt.query([])?;
-
-                while let Some(row) = rows.next()? {
-                    let sample_id: i64 = row.get(0)?;
-                    let text: String = row.get(1)?;
-                    let url: String = row.get(2)?;
-                    let text_lower = text.to_ascii_lowercase();
-
-                    matching_data.push(MachingSampleData {
-                        filename: url,
-                        text,
-                        text_lower,
-                        sample_id,
-                    });
-                }
-            }
+    let entries = get_dupes(
+        db,
+        args,
+        "SELECT hash_id FROM files",
+        "SELECT song_id, url, format, channel_count, file_size, sample_count FROM files where hash_id = ?",
+        0,
+    )?;
 
-            print!(
-                "{:02} {}",
-                sample.sample_id,
-                &sample.text[1..sample.text.len() - 1]
-            );
+    for (_index, v) in entries.iter().enumerate() {
+        for e in v {
+            println!("{}", get_url(&e.filename));
 
-            for _ in sample.text.chars().count()..max_len - 1 {
-                print!(" ");
+            if filters.sample_search.is_some() || args.print_sample_names {
+                print_samples_with_outline(&e.samples, &filters.sample_search, color);
             }
 
-            if !matching_data.is_empty() {
-                println!(
-                    "({} duplicates) length {}",
-                    matching_data.len(),
-                    sample.length
-                );
-            } else {
-                println!("length {}", sample.length);
+            if filters.instrument_search.is_some() || args.print_instrument_names {
+                print_samples_with_outline(&e.instrument_names, &filters.instrument_search, color);
             }
+        }
+    }
 
-            if !matching_data.is_empty() {
-                matching_data.sort_by(|a, b| b.text_lower.cmp(&a.text_lower));
+    Ok(())
+}
 
-                let t = TopSampleData {
-                    original_sample_id: sample.sample_id as _,
-                    text: sample.text.to_owned(),
-                    matching_samples: matching_data,
-                };
+// Collects, per song, the set of non-null sample hashes along with the song's url.
+fn get_song_sample_sets(db: &Connection) -> Result<HashMap<u64, (String, HashSet<String>)>> {
+    let mut songs: HashMap<u64, (String, HashSet<String>)> = HashMap::new();
 
-                top_samples.push(t);
-            }
-        }
+    let mut stmnt = db.prepare(
+        "SELECT samples.song_id, files.url, samples.hash_id \
+         FROM samples JOIN files ON samples.song_id = files.song_id \
+         WHERE samples.hash_id IS NOT NULL",
+    )?;
+    let mut rows = stmnt.query([])?;
 
-        for i in top_samples {
-            println!(
-                "-------------------------------------------------------------------------------"
-            );
-            println!("{:02} {}", i.original_sample_id, i.text);
-            println!(
-                "-------------------------------------------------------------------------------"
-            );
-            let mut max_len = 0;
-            for m in &i.matching_samples {
-                max_len = std::cmp::max(m.text.chars().count(), max_len);
-            }
+    while let Some(row) = rows.next()? {
+        let song_id: u64 = row.get(0)?;
+        let url: String = row.get(1)?;
+        let hash_id: String = row.get(2)?;
+
+        songs
+            .entry(song_id)
+            .or_insert_with(|| (url, HashSet::new()))
+            .1
+            .insert(hash_id);
+    }
 
-            max_len += 2;
+    Ok(songs)
+}
 
-            for m in &i.matching_samples {
-                print!("{:02} {}", m.sample_id, m.text);
+// Integrity check between a local mirror and the database it was supposedly built from: files
+// on disk with no matching database entry, database entries with no matching file on disk, and
+// files present on both sides whose sha256 no longer agrees (re-downloaded/corrupted/edited
+// since the database was built). Paths are compared the same way --build-database derives them
+// (relative to `dir`, URL-encoded), so `dir` should be the same root the database was built from.
+fn print_verify_report(dir: &str, args: &Args, db: &Connection) -> Result<()> {
+    let errors = RunErrors::new();
+    let filters = Filters::new(args);
+    let local_files = get_files(
+        dir,
+        args.recursive,
+        &errors,
+        args.fail_fast,
+        args.min_size,
+        args.max_size,
+        &args.files_from,
+        args.progress == "json",
+        args.max_depth,
+        args.follow_symlinks,
+        args.skip_hidden,
+        &args.ignore_patterns,
+        &filters,
+    );
 
-                for _ in m.text.chars().count()..max_len - 1 {
-                    print!(" ");
-                }
+    let mut local_by_url: HashMap<String, String> = HashMap::new();
+    for input_path in &local_files {
+        let relative = normalize_stored_path(input_path, dir, args);
+        local_by_url.insert(get_stored_url(&relative), input_path.clone());
+    }
 
-                println!("{}", m.filename);
+    let mut stmnt = db.prepare("SELECT hash_id, url FROM files")?;
+    let mut rows = stmnt.query([])?;
+
+    let mut db_by_url: HashMap<String, String> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let hash_id: String = row.get(0)?;
+        let url: String = row.get(1)?;
+        db_by_url.insert(url, hash_id);
+    }
+
+    let mut missing_from_db = Vec::new();
+    let mut changed = Vec::new();
+
+    for (url, path) in &local_by_url {
+        let Some(hash_id) = db_by_url.get(url) else {
+            missing_from_db.push(url.clone());
+            continue;
+        };
+
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                if args.fail_fast {
+                    panic!("Failed to read \"{}\": {}", path, err);
+                }
+                errors.record(path, &err);
+                continue;
             }
+        };
+
+        let hash = format!("{:x}", sha2::Sha256::digest(&data));
+        if &hash != hash_id {
+            changed.push(url.clone());
         }
     }
 
-    Ok(())
-}
+    let mut missing_on_disk: Vec<&String> =
+        db_by_url.keys().filter(|url| !local_by_url.contains_key(*url)).collect();
 
-// First check if we have a database next to the to the exe, otherwise try local directory
-fn check_for_db_file() -> Option<PathBuf> {
-    let path = Path::new(&get_db_filename()).to_path_buf();
-    if path.exists() {
-        Some(path)
-    } else {
-        None
+    missing_from_db.sort();
+    missing_on_disk.sort();
+    changed.sort();
+
+    println!("== Missing from database ({}) ==", missing_from_db.len());
+    for url in &missing_from_db {
+        println!("{}", get_url(url));
     }
-}
 
-fn get_dupes(
-    db: &Connection,
-    args: &Args,
-    get_songs_query: &str,
-    get_by_id: &str,
-    dupe_limit: usize,
-) -> Result<Vec<Vec<DatabaseMeta>>> {
-    let mut hash_dupes = Vec::with_capacity(700_0000);
-    let filters = Filters::new(args);
+    println!("\n== Missing on disk ({}) ==", missing_on_disk.len());
+    for url in &missing_on_disk {
+        println!("{}", get_url(url));
+    }
 
-    let mut stmnt = db.prepare(get_songs_query)?;
+    println!("\n== Changed sha256 ({}) ==", changed.len());
+    for url in &changed {
+        println!("{}", get_url(url));
+    }
+
+    errors.finish();
+
+    Ok(())
+}
+
+// Lists every database entry whose parse_status isn't "ok", grouped by format so maintainers
+// can see at a glance which formats would benefit most from a new/better backend.
+fn print_unparsed_report(db: &Connection) -> Result<()> {
+    let mut stmnt = db.prepare(
+        "SELECT format, parse_status, parse_error, url FROM files \
+         WHERE parse_status IS NOT NULL AND parse_status != 'ok' \
+         ORDER BY format, url",
+    )?;
     let mut rows = stmnt.query([])?;
 
-    let mut stmnt = db.prepare(get_by_id)?;
-    let mut hash_id_lookup_string = HashSet::new();
-    let mut hash_id_lookup_int = HashSet::new();
+    let mut by_format: HashMap<String, Vec<(String, Option<String>, String)>> = HashMap::new();
 
     while let Some(row) = rows.next()? {
-        let v = row.get_ref(0)?;
-        let mut vals = Vec::with_capacity(10);
-        let mut song_ids = Vec::with_capacity(10);
-
-        let mut song_rows = match v {
-            ValueRef::Null => continue,
-            ValueRef::Integer(v) => {
-                let v = v as u64;
-                if let Some(_v) = hash_id_lookup_int.get(&v) {
-                    continue;
-                } else {
-                    hash_id_lookup_int.insert(v);
-                }
+        let format: String = row.get(0)?;
+        let parse_status: String = row.get(1)?;
+        let parse_error: Option<String> = row.get(2)?;
+        let url: String = row.get(3)?;
+
+        by_format
+            .entry(format)
+            .or_default()
+            .push((parse_status, parse_error, url));
+    }
 
-                stmnt.query(params![v])?
-            }
+    if by_format.is_empty() {
+        println!("No unparsed entries found!");
+        return Ok(());
+    }
 
-            ValueRef::Text(v) => {
-                let v = std::str::from_utf8(v)?.to_owned();
+    let mut formats: Vec<&String> = by_format.keys().collect();
+    formats.sort();
 
-                if let Some(_v) = hash_id_lookup_string.get(&v) {
-                    continue;
-                } else {
-                    hash_id_lookup_string.insert(v.clone());
-                }
+    for format in formats {
+        let entries = &by_format[format];
+        println!(
+            "\n== .{} ({} entries) ==",
+            if format.is_empty() { "<none>" } else { format },
+            entries.len()
+        );
 
-                stmnt.query(params![v])?
+        for (parse_status, parse_error, url) in entries {
+            match parse_error {
+                Some(reason) => println!("[{}] {}: {}", parse_status, get_url(url), reason),
+                None => println!("[{}] {}", parse_status, get_url(url)),
             }
+        }
+    }
 
-            _ => panic!(),
-        };
+    Ok(())
+}
 
-        while let Some(row) = song_rows.next()? {
-            let song_id: u64 = row.get(0)?;
-            let filename: String = row.get(1)?;
-            let metadata = DatabaseMeta {
-                filename,
-                samples: Vec::new(),
-            };
-            vals.push(metadata);
-            song_ids.push(song_id);
-        }
+// Finds songs whose sample set strictly contains another song's sample set, grouped by the
+// containing (superset) song. Only songs that share at least one sample are ever compared,
+// since a bare O(n^2) walk over the whole database isn't feasible here.
+fn print_superset_report(db: &Connection) -> Result<()> {
+    let songs = get_song_sample_sets(db)?;
 
-        if vals.len() <= dupe_limit {
-            continue;
+    let mut hash_to_songs: HashMap<&String, Vec<u64>> = HashMap::new();
+    for (song_id, (_, samples)) in &songs {
+        for hash in samples {
+            hash_to_songs.entry(hash).or_default().push(*song_id);
         }
+    }
 
-        if filters.sample_search.is_some() || args.print_sample_names {
-            for (metadata, song_id) in vals.iter_mut().zip(song_ids.iter()) {
-                let t = get_samples_from_song_id(db, *song_id)?;
-                metadata.samples = t;
+    let mut candidate_pairs: HashSet<(u64, u64)> = HashSet::new();
+    for song_ids in hash_to_songs.values() {
+        for i in 0..song_ids.len() {
+            for j in (i + 1)..song_ids.len() {
+                let (a, b) = (song_ids[i], song_ids[j]);
+                candidate_pairs.insert((std::cmp::min(a, b), std::cmp::max(a, b)));
             }
         }
+    }
+
+    let mut found = 0;
+
+    for (a, b) in candidate_pairs {
+        let (url_a, samples_a) = &songs[&a];
+        let (url_b, samples_b) = &songs[&b];
+
+        if samples_a.len() == samples_b.len() {
+            // Equal-size sets are either identical or incomparable, not a strict superset.
+            continue;
+        }
 
-        let mut vals = filters.apply_filter(&vals, dupe_limit + 1);
+        let (superset_url, subset_url, superset, subset) = if samples_a.len() > samples_b.len() {
+            (url_a, url_b, samples_a, samples_b)
+        } else {
+            (url_b, url_a, samples_b, samples_a)
+        };
 
-        if !vals.is_empty() {
-            vals.sort_by(|a, b| a.filename.cmp(&b.filename));
-            hash_dupes.push(vals);
+        if subset.is_subset(superset) {
+            found += 1;
+            println!("\n==================================================================");
+            println!("Superset ({} samples): {}", superset.len(), get_url(superset_url));
+            println!("  contains ({} samples): {}", subset.len(), get_url(subset_url));
         }
     }
 
-    hash_dupes.sort_by(|a, b| a[0].filename.cmp(&b[0].filename));
+    if found == 0 {
+        println!("No superset/subset relationships found!");
+    }
 
-    Ok(hash_dupes)
+    Ok(())
 }
 
-fn print_db_duplicates(db: &Connection, args: &Args) -> Result<()> {
-    let filters = Filters::new(args);
+// The directory portion of a database `filename` path (e.g. "/pub/modules/x/y.mod" ->
+// "/pub/modules/x"), used to aggregate duplicate groups by folder instead of per-file.
+fn dir_of(filename: &str) -> &str {
+    match filename.rfind('/') {
+        Some(idx) => &filename[..idx],
+        None => "",
+    }
+}
 
+// Aggregates database duplicates by directory: for every pair of duplicated files living in
+// different directories, tallies how many such pairs point from one directory into another, then
+// reports every (source, target) directory pair as a percentage of all the source directory's
+// cross-directory duplicate pairs, highest percentage first.
+// This surfaces whole duplicated folders (e.g. a mirrored "favourites" dir) that would otherwise
+// get lost in a long per-file duplicate listing.
+fn print_dir_duplicate_summary(db: &Connection, args: &Args) -> Result<()> {
     let hash_dupes = get_dupes(
         db,
         args,
         "SELECT hash_id FROM files",
-        "SELECT song_id, url FROM files where hash_id = ?",
-        1,
+        "SELECT song_id, url, format, channel_count, file_size, sample_count FROM files where hash_id = ?",
+        args.min_dupes.saturating_sub(1),
     )?;
 
-    let pattern_dupes = get_dupes(
-        db,
-        args,
-        "SELECT pattern_hash FROM files",
-        "SELECT song_id, url FROM files where pattern_hash = ?",
-        1,
-    )?;
+    let mut pair_counts: HashMap<(String, String), usize> = HashMap::new();
+    let mut dir_totals: HashMap<String, usize> = HashMap::new();
 
-    for (index, v) in hash_dupes.iter().enumerate() {
-        println!("\n==================================================================");
-        println!("Dupe Entry {} (hash)", index);
+    for group in &hash_dupes {
+        for e in group {
+            let dir_a = dir_of(&e.filename);
 
-        for e in v {
-            println!("{}", get_url(&e.filename));
+            for other in group {
+                let dir_b = dir_of(&other.filename);
+                if other.filename == e.filename || dir_a == dir_b {
+                    continue;
+                }
 
-            if filters.sample_search.is_some() || args.print_sample_names {
-                print_samples_with_outline(&e.samples, &filters.sample_search);
+                *pair_counts
+                    .entry((dir_a.to_string(), dir_b.to_string()))
+                    .or_insert(0) += 1;
+                *dir_totals.entry(dir_a.to_string()).or_insert(0) += 1;
             }
         }
     }
 
-    for (index, v) in pattern_dupes.iter().enumerate() {
-        println!("\n==================================================================");
-        println!("Dupe Entry {} (pattern_hash)", index);
+    if pair_counts.is_empty() {
+        println!("No cross-directory duplicates found!");
+        return Ok(());
+    }
 
-        for e in v {
-            println!("{}", get_url(&e.filename));
+    let mut rows: Vec<(&String, &String, usize, usize)> = pair_counts
+        .iter()
+        .map(|((dir_a, dir_b), count)| (dir_a, dir_b, *count, dir_totals[dir_a]))
+        .collect();
 
-            if filters.sample_search.is_some() || args.print_sample_names {
-                print_samples_with_outline(&e.samples, &filters.sample_search);
-            }
-        }
+    rows.sort_by(|a, b| {
+        let pct_a = a.2 as f64 / a.3 as f64;
+        let pct_b = b.2 as f64 / b.3 as f64;
+        pct_b.partial_cmp(&pct_a).unwrap()
+    });
+
+    for (dir_a, dir_b, count, total) in rows {
+        let pct = (count as f64 / total as f64) * 100.0;
+        println!(
+            "{:.0}% of {} ({}/{} files) duplicates files under {}",
+            pct, dir_a, count, total, dir_b
+        );
     }
 
     Ok(())
 }
 
-fn print_db(db: &Connection, args: &Args) -> Result<()> {
-    let filters = Filters::new(args);
+// For --audit-pattern-collisions: a pattern_hash group is only a *likely* collision (as opposed
+// to a real duplicate) if its members don't agree on basic structural facts that an actual
+// identical arrangement would share regardless of container differences.
+fn looks_like_collision(group: &[DatabaseMeta]) -> bool {
+    let first = &group[0];
+    group
+        .iter()
+        .any(|e| e.channel_count != first.channel_count || e.sample_count != first.sample_count)
+}
 
-    let entries = get_dupes(
+// Re-parses a group's members under `reverify_dir` (resolved the same way --verify resolves
+// local paths) and recomputes their masked pattern_hash, to tell a real collision (two different
+// pattern_hashes landed in the same stored-group query only because one member's stored hash was
+// stale) from a confirmed one (every local copy we could find genuinely hashes the same). Members
+// whose local file can't be found are left unresolved rather than counted either way.
+fn reverify_pattern_hash_group(group: &[DatabaseMeta], reverify_dir: &str, args: &Args) -> Result<bool> {
+    let errors = RunErrors::new();
+    let mut hashes = HashSet::new();
+    let mut found_any = false;
+
+    for entry in group {
+        let path = Path::new(reverify_dir).join(get_stored_url(&entry.filename).trim_start_matches('/'));
+        if !path.exists() {
+            continue;
+        }
+
+        found_any = true;
+        let Some(path_str) = path.to_str() else { continue };
+        let info = get_track_info(
+            path_str,
+            args.dump_patterns,
+            false,
+            false,
+            args.render_fingerprint_seconds,
+            &args.sample_charset,
+            &errors,
+            args.fail_fast,
+        );
+        hashes.insert(info.pattern_hash & 0x7FFF_FFFF_FFFF_FFFF);
+    }
+
+    errors.finish();
+
+    // A real collision shows up as more than one distinct pattern_hash among the local copies we
+    // could actually re-parse; if we couldn't find any, there's nothing to confirm either way.
+    Ok(found_any && hashes.len() > 1)
+}
+
+fn print_audit_pattern_collisions(db: &Connection, args: &Args) -> Result<()> {
+    let pattern_query = if args.include_empty_patterns {
+        "SELECT pattern_hash FROM files"
+    } else {
+        "SELECT pattern_hash FROM files WHERE is_empty_pattern IS NOT 1"
+    };
+
+    let pattern_dupes = get_dupes(
         db,
         args,
-        "SELECT hash_id FROM files",
-        "SELECT song_id, url FROM files where hash_id = ?",
-        0,
+        pattern_query,
+        "SELECT song_id, url, format, channel_count, file_size, sample_count FROM files where pattern_hash = ?",
+        args.min_dupes.saturating_sub(1),
     )?;
 
-    for (_index, v) in entries.iter().enumerate() {
-        for e in v {
-            println!("{}", get_url(&e.filename));
+    let mut true_dupes = 0;
+    let mut flagged = 0;
+    let mut confirmed_collisions = 0;
 
-            if filters.sample_search.is_some() || args.print_sample_names {
-                print_samples_with_outline(&e.samples, &filters.sample_search);
+    for group in &pattern_dupes {
+        if !looks_like_collision(group) {
+            true_dupes += 1;
+            continue;
+        }
+
+        flagged += 1;
+
+        println!("==================================================================");
+        println!("Likely pattern_hash collision ({} entries):", group.len());
+        for e in group {
+            println!("  {} (channels={}, samples={})", get_url(&e.filename), e.channel_count, e.sample_count);
+        }
+
+        if let Some(reverify_dir) = args.audit_reverify_dir.as_ref() {
+            if reverify_pattern_hash_group(group, reverify_dir, args)? {
+                confirmed_collisions += 1;
+                println!("  -> confirmed: re-parsed local copies produced different pattern_hash values");
+            } else {
+                println!("  -> unconfirmed: re-parsing found no disagreement (or no local copies under {})", reverify_dir);
             }
         }
     }
 
+    println!("==================================================================");
+    println!(
+        "{} true duplicate group(s), {} flagged as likely collisions{}",
+        true_dupes,
+        flagged,
+        if args.audit_reverify_dir.is_some() {
+            format!(", {} confirmed", confirmed_collisions)
+        } else {
+            String::new()
+        }
+    );
+
     Ok(())
 }
 
@@ -1242,7 +6668,7 @@ fn print_sample_rows(rows: &mut rusqlite::Rows, args: &Args) -> Result<()> {
         let url: String = row.get(2)?;
 
         if let Some(re) = sample_search.as_ref() {
-            if !re.is_match(&text.to_ascii_lowercase()) {
+            if !re.is_match(&text.to_ascii_lowercase()) || !sample_name_class_allows(&args.sample_name_class, &text) {
                 continue;
             }
         }
@@ -1255,11 +6681,15 @@ fn print_sample_rows(rows: &mut rusqlite::Rows, args: &Args) -> Result<()> {
             text,
             text_lower,
             sample_id,
+            fine_tune: 0,
+            c5_speed: 0,
         });
     }
 
     if data.is_empty() {
-        println!("No matches found!");
+        if !args.quiet {
+            println!("No matches found!");
+        }
         return Ok(());
     }
 
@@ -1297,7 +6727,7 @@ fn match_db_with_sample_length(db: &Connection, args: &Args, length: usize) -> R
 fn match_db_with_sample_length_bytes(db: &Connection, args: &Args, length: usize) -> Result<()> {
     let statement = format!(
         "
-        SELECT song_sample_id, text, files.url 
+        SELECT song_sample_id, text, files.url
         FROM samples JOIN files ON samples.song_id = files.song_id WHERE samples.length_bytes = {}",
         length
     );
@@ -1308,16 +6738,320 @@ fn match_db_with_sample_length_bytes(db: &Connection, args: &Args, length: usize
     print_sample_rows(&mut rows, args)
 }
 
+// Pulls the raw bytes out of a WAV's "data" chunk, ignoring every other chunk (fmt, LIST, ...).
+// write_wav_file() writes sample PCM out verbatim with no conversion, so hashing this chunk
+// reproduces the exact sha256 the database stored for the original sample.
+fn read_wav_data_chunk(path: &str) -> Result<Vec<u8>> {
+    let data = std::fs::read(path)?;
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        bail!("\"{}\" is not a RIFF/WAVE file", path);
+    }
+
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let chunk_id = &data[offset..offset + 4];
+        let chunk_size = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let chunk_start = offset + 8;
+
+        if chunk_id == b"data" {
+            let chunk_end = std::cmp::min(chunk_start + chunk_size, data.len());
+            return Ok(data[chunk_start..chunk_end].to_vec());
+        }
+
+        // Chunks are padded to an even number of bytes.
+        offset = chunk_start + chunk_size + (chunk_size % 2);
+    }
+
+    bail!("\"{}\" has no data chunk", path)
+}
+
+// --find-sample accepts either a sha256 directly or a WAV file to hash, so a lone sample found
+// on disk can be traced back to every modland song that carries it without first building a
+// throwaway module around it.
+fn find_sample(db: &Connection, args: &Args, query: &str) -> Result<()> {
+    let hash = if query.len() == 64 && query.chars().all(|c| c.is_ascii_hexdigit()) {
+        query.to_ascii_lowercase()
+    } else {
+        let data = read_wav_data_chunk(query)?;
+        format!("{:x}", sha2::Sha256::digest(&data))
+    };
+
+    let statement = format!(
+        "
+        SELECT song_sample_id, text, files.url
+        FROM samples JOIN files ON samples.song_id = files.song_id WHERE samples.hash_id = '{}'",
+        hash
+    );
+
+    let mut stmnt = db.prepare(&statement)?;
+    let mut rows = stmnt.query([])?;
+
+    print_sample_rows(&mut rows, args)
+}
+
+// Applies the regex in Rust rather than via FTS5 MATCH so --search-message gets the same regex
+// semantics as --include-sample-name/--search-filename instead of FTS5's own match-query syntax.
+fn search_message(db: &Connection, args: &Args, pattern: &str) -> Result<()> {
+    let re = Regex::new(&pattern.to_ascii_lowercase())?;
+
+    let mut stmnt =
+        db.prepare("SELECT files.url, messages.text FROM messages JOIN files ON messages.song_id = files.song_id")?;
+    let mut rows = stmnt.query([])?;
+
+    let mut found = 0;
+
+    while let Some(row) = rows.next()? {
+        let url: String = row.get(0)?;
+        let text: String = row.get(1)?;
+
+        if !re.is_match(&text.to_ascii_lowercase()) {
+            continue;
+        }
+
+        found += 1;
+        println!("{}\n{}\n", url, text);
+    }
+
+    if found == 0 && !args.quiet {
+        println!("No matches found!");
+    }
+
+    Ok(())
+}
+
+// Maps a config-file key to the long flag that seeds it, restricted to the filter/listing flags
+// users tend to repeat on every run (path/extension/regex filters, channel/format/size/dupe
+// bounds, sort/color/output-format); one-shot action flags (--build-database, --list-database,
+// ...) aren't sourced from the config file.
+const CONFIG_KEY_TO_FLAG: &[(&str, &str)] = &[
+    ("include_paths", "include-paths"),
+    ("exclude_paths", "exclude-paths"),
+    ("include_file_extensions", "include-file-extensions"),
+    ("exclude_file_extensions", "exclude-file-extensions"),
+    ("include_path_regex", "include-path-regex"),
+    ("exclude_path_regex", "exclude-path-regex"),
+    ("include_sample_name", "include-sample-name"),
+    ("include_instrument_name", "include-instrument-name"),
+    ("search_filename", "search-filename"),
+    ("channels", "channels"),
+    ("format", "format"),
+    ("min_size", "min-size"),
+    ("max_size", "max-size"),
+    ("min_dupes", "min-dupes"),
+    ("max_dupes", "max-dupes"),
+    ("color", "color"),
+    ("sort", "sort"),
+    ("output_format", "output-format"),
+];
+
+// Parses a minimal TOML-like config file: blank lines and `#` comments are ignored, every other
+// line is `key = value`, where value is a quoted string, a bare number/bool, or a `["a", "b"]`
+// array of quoted strings (joined with commas to match the comma-separated CLI flags, e.g.
+// --include-paths "a,b"). Full TOML (tables, multiline strings, ...) isn't needed for the flat
+// set of filter flags this seeds.
+fn parse_config_file(content: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_string();
+        let value = value.trim();
+
+        let value = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            Some(inner) => inner
+                .split(',')
+                .map(|item| item.trim().trim_matches('"').to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => value.trim_matches('"').to_string(),
+        };
+
+        values.insert(key, value);
+    }
+
+    values
+}
+
+// Extracts `db_filename`/`db_remote` out of the config file's `[profiles.<name>]` section, the
+// table-header convention parse_config_file's flat key=value lines otherwise just skip over (no
+// '=' to split on). Only that one section is collected; anything before the first matching header
+// or after the next "[...]" header is ignored.
+fn parse_profile_section(content: &str, profile_name: &str) -> HashMap<String, String> {
+    let header = format!("[profiles.{}]", profile_name);
+    let mut values = HashMap::new();
+    let mut in_section = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        if line.starts_with('[') {
+            in_section = line == header;
+            continue;
+        }
+
+        if !in_section || line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        values.insert(key.trim().to_string(), value.trim().trim_matches('"').to_string());
+    }
+
+    values
+}
+
+// Default config file location, mirroring XDG-style per-user config directories.
+fn default_config_path() -> Option<PathBuf> {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok()?;
+    Some(Path::new(&home).join(".config/modland_hash/config.toml"))
+}
+
+// Inserts the config file's flags right after argv[0] (the binary name) so any matching flag
+// later in the real command line overrides it (clap keeps the last occurrence of a scalar flag).
+// `--config <path>` on the real command line is honored; otherwise falls back to
+// --config/default_config_path() scanned directly out of argv, since this runs before clap has
+// parsed anything.
+fn seed_argv_with_config(argv: Vec<String>) -> Vec<String> {
+    let config_path = argv
+        .iter()
+        .position(|a| a == "--config")
+        .and_then(|i| argv.get(i + 1))
+        .map(PathBuf::from)
+        .or_else(default_config_path);
+
+    let Some(config_path) = config_path else {
+        return argv;
+    };
+
+    let Ok(content) = std::fs::read_to_string(&config_path) else {
+        return argv;
+    };
+
+    let config_values = parse_config_file(&content);
+    let mut seeded = vec![argv[0].clone()];
+
+    for (key, flag) in CONFIG_KEY_TO_FLAG {
+        if let Some(value) = config_values.get(*key) {
+            seeded.push(format!("--{}", flag));
+            seeded.push(value.clone());
+        }
+    }
+
+    seeded.extend(argv.into_iter().skip(1));
+    seeded
+}
+
 fn main() -> Result<()> {
-    let args = Args::parse();
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Off)
-        .init()?;
+    let args = Args::parse_from(seed_argv_with_config(std::env::args().collect()));
+
+    if let Some(shell) = args.generate_completions {
+        let mut cmd = Args::command();
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    if args.generate_man {
+        clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout())?;
+        return Ok(());
+    }
+
+    URL_PREFIX.set(args.url_prefix.clone()).ok();
+
+    if let Some(profile_name) = args.profile.as_ref() {
+        let config_path =
+            args.config.as_ref().map(PathBuf::from).or_else(default_config_path);
+
+        let Some(config_path) = config_path else {
+            bail!(
+                "--profile {} given but no config file found (use --config or ~/.config/modland_hash/config.toml)",
+                profile_name,
+            );
+        };
+
+        let content = std::fs::read_to_string(&config_path).map_err(|err| {
+            anyhow::anyhow!("--profile {} given but {} couldn't be read: {}", profile_name, config_path.display(), err)
+        })?;
+
+        let profile_values = parse_profile_section(&content, profile_name);
+        if profile_values.is_empty() {
+            bail!("No [profiles.{}] section found in {}", profile_name, config_path.display());
+        }
+
+        if let Some(db_filename) = profile_values.get("db_filename") {
+            PROFILE_DB_FILENAME.set(db_filename.clone()).ok();
+        }
+        if let Some(db_remote) = profile_values.get("db_remote") {
+            PROFILE_DB_REMOTE.set(db_remote.clone()).ok();
+        }
+    }
+
+    if !matches!(args.backend.as_str(), "auto" | "libopenmpt" | "sid") {
+        bail!(
+            "--backend {} is not supported; only \"auto\", \"libopenmpt\" and \"sid\" are available",
+            args.backend
+        );
+    }
+    if args.backend != "auto" {
+        BACKEND_OVERRIDE.set(args.backend.clone()).ok();
+    }
+
+    if !matches!(args.db_format.as_str(), "auto" | "7z" | "zstd") {
+        bail!(
+            "--db-format {} is not supported; only \"auto\", \"7z\" and \"zstd\" are available",
+            args.db_format
+        );
+    }
+
+    if !matches!(args.sample_name_class.as_str(), "any" | "name" | "message") {
+        bail!(
+            "--sample-name-class {} is not supported; only \"any\", \"name\" and \"message\" are available",
+            args.sample_name_class
+        );
+    }
+
+    let log_level = match args.verbose {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Info,
+        _ => log::LevelFilter::Debug,
+    };
+
+    SimpleLogger::new().with_level(log_level).init()?;
+
+    install_shutdown_handler();
+
+    // Internal re-entry point used by --worker-pool: parse one file and report back over
+    // stdout, instead of running the normal build/match commands below.
+    if let Some(filename) = args.parse_worker.as_ref() {
+        return run_parse_worker(
+            filename,
+            args.dump_patterns,
+            args.samples_only,
+            args.no_sample_hashes,
+            args.render_fingerprint_seconds,
+            &args.sample_charset,
+        );
+    }
 
     // first we check if we have a database and if we don't we try to download it we don't
     // or if the database version doesn't match
 
     if let Some(db_path) = args.build_database.as_ref() {
+        if args.dry_run {
+            print_build_database_dry_run(db_path, &args);
+            return Ok(());
+        }
+
         let filename = get_db_filename();
 
         if std::path::Path::new(&filename).exists() {
@@ -1329,16 +7063,96 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(outdir) = args.export_samples.as_ref() {
+        return export_samples(&args.match_dir, outdir, &args);
+    }
+
+    if let Some(files) = args.diff_patterns.as_ref() {
+        return diff_patterns(&files[0], &files[1], &args);
+    }
+
+    if let Some(dir) = args.bench.as_ref() {
+        return run_benchmark(dir, &args);
+    }
+
+    if args.check_update {
+        return check_update();
+    }
+
+    if let Some(out_filename) = args.package_database.as_ref() {
+        return package_database(out_filename);
+    }
+
     let database_path = check_for_db_file();
 
     if args.download_database || database_path.is_none() {
-        let pb = download_db()?;
-        decompress_db(Some(pb))?;
-    } else {
+        if let Some(old_db) = database_path.as_ref() {
+            if let Ok(old_conn) = Connection::open(old_db) {
+                if let Err(err) = export_tags(&old_conn, &get_tags_filename()) {
+                    log::warn!("Failed to preserve tags before re-download: {}", err);
+                }
+            }
+        }
+
+        match resolve_db_format(&args) {
+            "zstd" => {
+                let pb = download_db_zstd()?;
+                if !args.db_compressed {
+                    decompress_db_zstd(Some(pb))?;
+                }
+            }
+            _ if args.stream_download && !args.db_compressed => {
+                stream_download_and_decompress_db()?;
+            }
+            _ => {
+                let pb = download_db()?;
+                if !args.db_compressed {
+                    decompress_db(Some(pb))?;
+                }
+            }
+        }
+    } else if !args.db_compressed {
+        // Finishes unpacking a compressed artifact left over from an interrupted previous run,
+        // whichever format it happens to be in; both are no-ops if their file isn't there.
         decompress_db(None)?;
+        decompress_db_zstd(None)?;
+    }
+
+    let conn = if args.db_compressed {
+        open_compressed_db_readonly(&args)?
+    } else {
+        Connection::open(get_db_filename())?
+    };
+
+    // Tags and annotations need a writable connection, which the scratch copy opened by
+    // --db-compressed deliberately isn't.
+    if !args.db_compressed {
+        ensure_tags_table(&conn)?;
+
+        if let Err(err) = import_tags(&conn, &get_tags_filename()) {
+            log::warn!("Failed to restore tags sidecar: {}", err);
+        }
+    }
+
+    if let Some(parts) = args.tag.as_ref() {
+        return apply_tag(&parts[0], &parts[1], &conn);
+    }
+
+    if let Some(tag) = args.filter_tag.as_ref() {
+        return print_tag_report(tag, &conn);
+    }
+
+    if !args.db_compressed {
+        ensure_annotations_table(&conn)?;
+    }
+
+    if let Some(path) = args.import_annotations.as_ref() {
+        return import_annotations(path, &conn);
     }
 
-    let conn = Connection::open(get_db_filename())?;
+    if let Some(path) = args.export_sample_graph.as_ref() {
+        return export_sample_graph(path, &conn);
+    }
 
     if let Some(len) = args.find_samples_with_length {
         return match_db_with_sample_length(&conn, &args, len);
@@ -1348,19 +7162,67 @@ fn main() -> Result<()> {
         return match_db_with_sample_length_bytes(&conn, &args, len);
     }
 
+    if let Some(query) = args.find_sample.as_ref() {
+        return find_sample(&conn, &args, query);
+    }
+
+    if let Some(pattern) = args.search_message.as_ref() {
+        return search_message(&conn, &args, pattern);
+    }
+
     // Process duplicates in the database
     if args.list_duplicates_in_database {
         return print_db_duplicates(&conn, &args);
     }
 
+    if args.find_supersets {
+        return print_superset_report(&conn);
+    }
+
+    if args.dir_duplicate_summary {
+        return print_dir_duplicate_summary(&conn, &args);
+    }
+
+    if args.audit_pattern_collisions {
+        return print_audit_pattern_collisions(&conn, &args);
+    }
+
+    if args.list_unparsed {
+        return print_unparsed_report(&conn);
+    }
+
+    if let Some(dir) = args.verify.as_ref() {
+        return print_verify_report(dir, &args, &conn);
+    }
+
+    if let Some(target) = args.export.as_ref() {
+        return export_database(target, &conn);
+    }
+
+    if let Some(path) = args.export_hashlist.as_ref() {
+        return export_hashlist(path, &conn);
+    }
+
     if args.match_samples {
         return match_samples(&args.match_dir, &conn, &args);
     }
 
+    if args.suggest_names {
+        return suggest_names(&args.match_dir, &args, &conn);
+    }
+
+    if let Some(parts) = args.import.as_ref() {
+        return import_new_files(&parts[0], &parts[1], &args, &conn);
+    }
+
     // Process duplicates in the database
     if args.list_database {
         return print_db(&conn, &args);
     }
 
+    if let Some(filename) = args.inspect.as_ref() {
+        return inspect_file(filename, &args, &conn);
+    }
+
     match_dir_against_db(&args.match_dir, &args, &conn)
 }